@@ -24,13 +24,20 @@ load(
 )
 load(
     "//device:defs.bzl",
+    _ciq_push_app_settings = "ciq_push_app_settings",
     _ciq_sideload_app = "ciq_sideload_app",
+    _ciq_tail_app_log = "ciq_tail_app_log",
+    _ciq_uninstall_app = "ciq_uninstall_app",
+    _ciq_view_app_crash_log = "ciq_view_app_crash_log",
     _ciq_view_app_log = "ciq_view_app_log",
     _ciq_view_app_profiling = "ciq_view_app_profiling",
 )
 load(
     "//simulator:defs.bzl",
+    _ciq_scenario_test = "ciq_scenario_test",
     _ciq_simulation = "ciq_simulation",
+    _ciq_simulator_sweep = "ciq_simulator_sweep",
+    _ciq_smoke_test = "ciq_smoke_test",
     _ciq_test = "ciq_test",
 )
 load(
@@ -59,12 +66,19 @@ supports_app_type = _supports_app_type
 supports_min_sdk = _supports_min_sdk
 
 # device/defs.bzl
+ciq_push_app_settings = _ciq_push_app_settings
 ciq_sideload_app = _ciq_sideload_app
+ciq_tail_app_log = _ciq_tail_app_log
+ciq_uninstall_app = _ciq_uninstall_app
+ciq_view_app_crash_log = _ciq_view_app_crash_log
 ciq_view_app_log = _ciq_view_app_log
 ciq_view_app_profiling = _ciq_view_app_profiling
 
 # simulator/defs.bzl
+ciq_scenario_test = _ciq_scenario_test
 ciq_simulation = _ciq_simulation
+ciq_simulator_sweep = _ciq_simulator_sweep
+ciq_smoke_test = _ciq_smoke_test
 ciq_test = _ciq_test
 
 # store/defs.bzl
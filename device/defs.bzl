@@ -5,6 +5,10 @@ Rules for interacting with physical Garmin devices.
 load("@bazel_skylib//lib:paths.bzl", "paths")
 load("//build:defs.bzl", "DeviceBuildInfo")
 
+# Larger than any settings or storage data file of an app, so that pushing
+# them compares the contents of every file rather than just its size.
+_MAX_APP_DATA_FILE_SIZE = 16 * 1024 * 1024
+
 def _calculatePrgPath(prg_file):
     """Calculates the destination path for the .prg file on the device.
 
@@ -40,6 +44,35 @@ def _calculatePrfPath(prg_file):
     prf_basename = paths.replace_extension(prg_file.basename, ".PRF")
     return paths.join("/GARMIN/Apps/LOGS", prf_basename)
 
+def _calculateSettingsPath(prg_file):
+    """Calculates the path for the settings file corresponding to the .prg file on the device.
+
+    Args:
+        prg_file: A .prg file.
+
+    Returns:
+        The absolute path on the device where the settings file is located.
+    """
+    set_basename = paths.replace_extension(prg_file.basename, ".SET")
+    return paths.join("/GARMIN/Apps/SETTINGS", set_basename)
+
+def _calculatePushedFilePath(prg_file, folder, extension):
+    """Calculates the path to push a settings or storage data file for the .prg file to.
+
+    The name is upper-cased. Device filesystems are case-insensitive, so this
+    only changes how a newly created file is listed.
+
+    Args:
+        prg_file: A .prg file.
+        folder: The folder on the device to push the file to.
+        extension: The extension of the file, e.g. ".SET".
+
+    Returns:
+        The absolute path on the device to push the file to.
+    """
+    basename = paths.replace_extension(prg_file.basename, extension).upper()
+    return paths.join(folder, basename)
+
 def _ciq_sideload_app_impl(ctx):
     device_build_info = ctx.attr.device_build[DeviceBuildInfo]
     output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
@@ -52,14 +85,26 @@ def _ciq_sideload_app_impl(ctx):
 
     # The "?" prefix on the log_dst path means that the upload will "soft-fail":
     # if the file already exists on the device, it will not be replaced.
+    # The LOGS folder may be missing on a freshly reset device, so --mkdirs is
+    # passed to create it.
     script_content = """
-        {tool} "{prg_src}" "{prg_dst}" "{log_src}" "?{log_dst}"
+        {tool} --mkdirs {check_args} "$@" "{prg_src}" "{prg_dst}" "{log_src}" "?{log_dst}"
     """
 
+    check_args = ""
+    check_files = []
+    if ctx.file.devices_json:
+        check_args = '--expect-device "{}" --devices-json "{}"'.format(
+            device_build_info.device_id,
+            ctx.file.devices_json.short_path,
+        )
+        check_files.append(ctx.file.devices_json)
+
     ctx.actions.write(
         output = output_script,
         content = script_content.format(
             tool = ctx.executable._mtp_upload_tool.short_path,
+            check_args = check_args,
             prg_src = device_build_info.prg_file.short_path,
             prg_dst = _calculatePrgPath(device_build_info.prg_file),
             log_src = empty_log_file.short_path,
@@ -76,14 +121,14 @@ def _ciq_sideload_app_impl(ctx):
                     empty_log_file,
                     output_script,
                     device_build_info.prg_file,
-                ] + ctx.attr._mtp_upload_tool.files.to_list(),
+                ] + check_files + ctx.attr._mtp_upload_tool.files.to_list(),
             ),
         ),
     ]
 
-ciq_sideload_app = rule(
+_ciq_sideload_app = rule(
     implementation = _ciq_sideload_app_impl,
-    doc = "Sideloads the application (.prg) to a connected physical Garmin device via MTP.",
+    doc = "Private rule to sideload an application to a device.",
     executable = True,
     attrs = {
         "device_build": attr.label(
@@ -91,6 +136,10 @@ ciq_sideload_app = rule(
             mandatory = True,
             providers = [DeviceBuildInfo],
         ),
+        "devices_json": attr.label(
+            doc = "The SDK's devices.json, used to refuse devices of a different model. Not checked if unset.",
+            allow_single_file = True,
+        ),
         "_mtp_upload_tool": attr.label(
             executable = True,
             cfg = "exec",
@@ -99,6 +148,180 @@ ciq_sideload_app = rule(
     },
 )
 
+def ciq_sideload_app(name, device_build, check_device = True, **kwargs):
+    """Sideloads the application (.prg) to a connected physical Garmin device via MTP.
+
+    When several devices are attached, pass `--serial` or `--name` after `--`
+    on the `bazel run` command line to choose one, or `--all-devices` to
+    sideload to all of them in parallel.
+
+    Pass `--sync` to skip re-sending a .prg whose size matches the copy already
+    on the device, adding `--compare-up-to <bytes>` to also compare contents.
+
+    The sideload is refused if the connected device is identified as a model
+    other than the one the build was compiled for.
+
+    Args:
+        name: The name of the target.
+        device_build: The ciq_device_build target to sideload.
+        check_device: Whether to refuse to sideload to a device of a different model than the build's device_id.
+        **kwargs: Standard Bazel rule arguments (tags, visibility, etc.).
+    """
+    _ciq_sideload_app(
+        name = name,
+        device_build = device_build,
+        devices_json = Label("@local_ciq//:devices.json") if check_device else None,
+        **kwargs
+    )
+
+def _ciq_push_app_settings_impl(ctx):
+    device_build_info = ctx.attr.device_build[DeviceBuildInfo]
+    output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
+
+    file_pairs = []
+    if ctx.file.settings:
+        dst = _calculatePushedFilePath(device_build_info.prg_file, "/GARMIN/Apps/SETTINGS", ".SET")
+        file_pairs.append((ctx.file.settings, dst))
+    for data_file in ctx.files.storage:
+        extension = "." + data_file.extension if data_file.extension else ""
+        dst = _calculatePushedFilePath(device_build_info.prg_file, "/GARMIN/Apps/DATA", extension)
+        file_pairs.append((data_file, dst))
+
+    # With --sync, a later file pushed to the same path would silently replace
+    # an earlier one. Device filesystems are case-insensitive.
+    sources_by_dst = {}
+    for src, dst in file_pairs:
+        if dst.upper() in sources_by_dst:
+            fail("{} and {} would both be pushed to {}; storage files must have different extensions".format(
+                sources_by_dst[dst.upper()].short_path,
+                src.short_path,
+                dst,
+            ))
+        sources_by_dst[dst.upper()] = src
+
+    # Sending a file does not replace an existing one of the same name, so
+    # --sync is passed to replace files that differ from those on the device.
+    # Edited settings often keep the same size, so contents are compared too.
+    # The SETTINGS and DATA folders may be missing until an app first uses
+    # them, so --mkdirs is passed to create them.
+    ctx.actions.write(
+        output = output_script,
+        content = """
+            {tool} --mkdirs --sync --compare-up-to {compare_up_to} "$@" {file_pairs}
+        """.format(
+            tool = ctx.executable._mtp_upload_tool.short_path,
+            compare_up_to = _MAX_APP_DATA_FILE_SIZE,
+            file_pairs = " ".join([
+                '"{}" "{}"'.format(src.short_path, dst)
+                for src, dst in file_pairs
+            ]),
+        ),
+        is_executable = True,
+    )
+
+    return [
+        DefaultInfo(
+            executable = output_script,
+            runfiles = ctx.runfiles(
+                files = [
+                    output_script,
+                ] + [src for src, _ in file_pairs] + ctx.attr._mtp_upload_tool.files.to_list(),
+            ),
+        ),
+    ]
+
+ciq_push_app_settings = rule(
+    implementation = _ciq_push_app_settings_impl,
+    doc = """Uploads application settings and storage data to a connected physical Garmin device via MTP.
+
+    The files are uploaded as they are, so they must already be in the binary
+    format the device reads, such as files downloaded from another device with
+    ciq_download_diagnostics. The files are renamed after the application's
+    .prg file, so that the app finds them on its next start, and replace those
+    on the device that differ.
+
+    Converting the -settings.json file pushed to the simulator into a .SET
+    file is not supported yet, as the .SET format is undocumented.
+
+    When several devices are attached, pass `--serial` or `--name` after `--`
+    on the `bazel run` command line to choose one.""",
+    executable = True,
+    attrs = {
+        "device_build": attr.label(
+            doc = "The ciq_device_build target whose settings and storage data to replace.",
+            mandatory = True,
+            providers = [DeviceBuildInfo],
+        ),
+        "settings": attr.label(
+            doc = "The settings file (.SET) to place in /GARMIN/Apps/SETTINGS.",
+            allow_single_file = [".SET", ".set"],
+        ),
+        "storage": attr.label_list(
+            doc = "Storage data files to place in /GARMIN/Apps/DATA, keeping their extensions, which must all differ.",
+            allow_files = True,
+        ),
+        "_mtp_upload_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//device:upload"),
+        ),
+    },
+)
+
+def _ciq_uninstall_app_impl(ctx):
+    device_build_info = ctx.attr.device_build[DeviceBuildInfo]
+    output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
+
+    # The "?" prefix on each path means that the delete will "soft-fail":
+    # files that are not on the device are skipped.
+    script_content = """
+        {tool} "$@" "?{prg_path}" "?{log_path}" "?{prf_path}" "?{settings_path}"
+    """
+
+    ctx.actions.write(
+        output = output_script,
+        content = script_content.format(
+            tool = ctx.executable._mtp_delete_tool.short_path,
+            prg_path = _calculatePrgPath(device_build_info.prg_file),
+            log_path = _calculateLogPath(device_build_info.prg_file),
+            prf_path = _calculatePrfPath(device_build_info.prg_file),
+            settings_path = _calculateSettingsPath(device_build_info.prg_file),
+        ),
+        is_executable = True,
+    )
+
+    return [
+        DefaultInfo(
+            executable = output_script,
+            runfiles = ctx.runfiles(
+                files = [
+                    output_script,
+                ] + ctx.attr._mtp_delete_tool.files.to_list(),
+            ),
+        ),
+    ]
+
+ciq_uninstall_app = rule(
+    implementation = _ciq_uninstall_app_impl,
+    doc = """Removes the application (.prg) and its log, profiling and settings files from a connected physical Garmin device via MTP.
+
+    When several devices are attached, pass `--serial` or `--name` after `--`
+    on the `bazel run` command line to choose one.""",
+    executable = True,
+    attrs = {
+        "device_build": attr.label(
+            doc = "The ciq_device_build target to uninstall.",
+            mandatory = True,
+            providers = [DeviceBuildInfo],
+        ),
+        "_mtp_delete_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//device:delete"),
+        ),
+    },
+)
+
 def _ciq_view_app_log_impl(ctx):
     device_build_info = ctx.attr.device_build[DeviceBuildInfo]
     output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
@@ -108,7 +331,7 @@ def _ciq_view_app_log_impl(ctx):
         output = output_script,
         content = """
             touch "{log_dst}"
-            {tool} "{log_src}" "{log_dst}"
+            {tool} "$@" "{log_src}" "{log_dst}"
             cat "{log_dst}"
         """.format(
             tool = ctx.executable._mtp_download_tool.short_path,
@@ -131,7 +354,10 @@ def _ciq_view_app_log_impl(ctx):
 
 ciq_view_app_log = rule(
     implementation = _ciq_view_app_log_impl,
-    doc = "Downloads and outputs the log file from a connected physical Garmin device.",
+    doc = """Downloads and outputs the log file from a connected physical Garmin device.
+
+    When several devices are attached, pass `--serial` or `--name` after `--`
+    on the `bazel run` command line to choose one.""",
     executable = True,
     attrs = {
         "device_build": attr.label(
@@ -147,6 +373,53 @@ ciq_view_app_log = rule(
     },
 )
 
+def _ciq_tail_app_log_impl(ctx):
+    device_build_info = ctx.attr.device_build[DeviceBuildInfo]
+    output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
+
+    ctx.actions.write(
+        output = output_script,
+        content = """
+            {tool} "$@" "{log_src}"
+        """.format(
+            tool = ctx.executable._mtp_tail_tool.short_path,
+            log_src = _calculateLogPath(device_build_info.prg_file),
+        ),
+        is_executable = True,
+    )
+
+    return [
+        DefaultInfo(
+            executable = output_script,
+            runfiles = ctx.runfiles(
+                files = [
+                    output_script,
+                ] + ctx.attr._mtp_tail_tool.files.to_list(),
+            ),
+        ),
+    ]
+
+ciq_tail_app_log = rule(
+    implementation = _ciq_tail_app_log_impl,
+    doc = """Follows the log file on a physical Garmin device, printing new output as it appears.
+
+    The device may be disconnected to run the app and reconnected to see its
+    latest output, without restarting the target. Press Ctrl-C to stop.""",
+    executable = True,
+    attrs = {
+        "device_build": attr.label(
+            doc = "The ciq_device_build target to follow the log file for.",
+            mandatory = True,
+            providers = [DeviceBuildInfo],
+        ),
+        "_mtp_tail_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//device:tail"),
+        ),
+    },
+)
+
 def _ciq_view_app_profiling_impl(ctx):
     device_build_info = ctx.attr.device_build[DeviceBuildInfo]
     output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
@@ -205,6 +478,167 @@ ciq_view_app_profiling = rule(
     },
 )
 
+def _ciq_view_app_crash_log_impl(ctx):
+    device_build_info = ctx.attr.device_build[DeviceBuildInfo]
+    output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
+    log_dst_path = "{}.YML".format(ctx.label.name)
+
+    # The "?" prefix on the crash log path means that the download will
+    # "soft-fail": a device that has never recorded a crash has no CIQ_LOG.YML,
+    # in which case the empty file is interpreted as having no crashes.
+    ctx.actions.write(
+        output = output_script,
+        content = """
+            rm -f "{log_dst}"
+            touch "{log_dst}"
+            {download_tool} "$@" "?/GARMIN/Apps/LOGS/CIQ_LOG.YML" "{log_dst}"
+            {interpret_tool} "{log_dst}" --debug-xml "{debug_xml}" --app "{app}"
+        """.format(
+            download_tool = ctx.executable._mtp_download_tool.short_path,
+            interpret_tool = ctx.executable._interpret_crash_log_tool.short_path,
+            log_dst = log_dst_path,
+            debug_xml = device_build_info.prg_debug_xml_file.short_path,
+            app = paths.split_extension(device_build_info.prg_file.basename)[0],
+        ),
+        is_executable = True,
+    )
+
+    return [
+        DefaultInfo(
+            executable = output_script,
+            runfiles = ctx.runfiles(
+                files = [
+                    output_script,
+                    device_build_info.prg_debug_xml_file,
+                ] + ctx.attr._mtp_download_tool.files.to_list() + ctx.attr._interpret_crash_log_tool.files.to_list(),
+            ),
+        ),
+    ]
+
+ciq_view_app_crash_log = rule(
+    implementation = _ciq_view_app_crash_log_impl,
+    doc = """Downloads the crash log (CIQ_LOG.YML) from the device and prints the application's crashes as symbolized stack traces.
+
+    Program counters are resolved to functions and source lines using the debug
+    XML of the given build, so it should match the build installed on the device.
+
+    When several devices are attached, pass `--serial` or `--name` after `--`
+    on the `bazel run` command line to choose one.""",
+    executable = True,
+    attrs = {
+        "device_build": attr.label(
+            doc = "The ciq_device_build target to retrieve crashes for.",
+            mandatory = True,
+            providers = [DeviceBuildInfo],
+        ),
+        "_mtp_download_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//device:download"),
+        ),
+        "_interpret_crash_log_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//device:interpret_crash_log"),
+        ),
+    },
+)
+
+def _ciq_identify_device_impl(ctx):
+    output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
+
+    ctx.actions.write(
+        output = output_script,
+        content = """
+            {tool} --devices-json "{devices_json}" "$@"
+        """.format(
+            tool = ctx.executable._identify_tool.short_path,
+            devices_json = ctx.file._devices_json.short_path,
+        ),
+        is_executable = True,
+    )
+
+    return [
+        DefaultInfo(
+            executable = output_script,
+            runfiles = ctx.runfiles(
+                files = [
+                    output_script,
+                    ctx.file._devices_json,
+                ] + ctx.attr._identify_tool.files.to_list(),
+            ),
+        ),
+    ]
+
+ciq_identify_device = rule(
+    implementation = _ciq_identify_device_impl,
+    doc = """Prints the model, serial number, firmware version and Connect IQ device IDs of a connected physical Garmin device.
+
+    When several devices are attached, pass `--serial` or `--name` after `--`
+    on the `bazel run` command line to choose one.""",
+    executable = True,
+    attrs = {
+        "_devices_json": attr.label(
+            allow_single_file = True,
+            default = Label("@local_ciq//:devices.json"),
+        ),
+        "_identify_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//device:identify"),
+        ),
+    },
+)
+
+def _ciq_download_diagnostics_impl(ctx):
+    output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
+
+    # Each pattern is prefixed with "?" so that folders missing on the device
+    # are skipped rather than failing the whole download.
+    ctx.actions.write(
+        output = output_script,
+        content = """
+            dst="${{BUILD_WORKING_DIRECTORY:-.}}/{dst}"
+            mkdir -p "$dst"
+            {tool} "$@" \\
+                "?/GARMIN/Apps/LOGS/*" "$dst/LOGS" \\
+                "?/GARMIN/Apps/SETTINGS/*" "$dst/SETTINGS" \\
+                "?/GARMIN/Apps/DATA/**" "$dst/DATA" \\
+                "?/GARMIN/GarminDevice.xml" "$dst/GarminDevice.xml"
+        """.format(
+            tool = ctx.executable._mtp_download_tool.short_path,
+            dst = ctx.label.name,
+        ),
+        is_executable = True,
+    )
+
+    return [
+        DefaultInfo(
+            executable = output_script,
+            runfiles = ctx.runfiles(
+                files = [
+                    output_script,
+                ] + ctx.attr._mtp_download_tool.files.to_list(),
+            ),
+        ),
+    ]
+
+ciq_download_diagnostics = rule(
+    implementation = _ciq_download_diagnostics_impl,
+    doc = """Downloads the logs, profiles, settings and app data of every app on a connected physical Garmin device.
+
+    The files are written to a directory named after the target, in the
+    directory `bazel run` was invoked from.""",
+    executable = True,
+    attrs = {
+        "_mtp_download_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//device:download"),
+        ),
+    },
+)
+
 def _ciq_view_system_log_impl(ctx):
     output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
     log_dst_path = "{}.txt".format(ctx.label.name)
@@ -214,7 +648,7 @@ def _ciq_view_system_log_impl(ctx):
         content = """
             rm -f "{log_dst}"
             touch "{log_dst}"
-            {tool} "/GARMIN/Apps/LOGS/CIQ_LOG.YML" "{log_dst}"
+            {tool} "$@" "/GARMIN/Apps/LOGS/CIQ_LOG.YML" "{log_dst}"
             cat "{log_dst}"
         """.format(
             tool = ctx.executable._mtp_download_tool.short_path,
@@ -0,0 +1,59 @@
+//! Deletes files from an MTP device.
+//!
+//! Usage:
+//!   bazel run @rules_ciq//device:delete <device_path> [<device_path> ...]
+//!
+//! Example:
+//!   bazel run @rules_ciq//device:delete /GARMIN/Apps/my_app.prg
+//!
+//! If the device path is prefixed with `?` (e.g. `?/GARMIN/Apps/my_app.prg`),
+//! the delete will "soft-fail": if the file does not exist on the device, the
+//! tool will exit successfully without deleting anything.
+//!
+//! Passing `--device-root <dir>` deletes from a local directory standing in
+//! for the device filesystem, instead of a connected MTP device.
+//!
+//! On failure, the exit status identifies the kind of error, e.g. 4 when the
+//! device is claimed by another process and 6 when a file is missing. See
+//! `error.rs` for the full list.
+
+use clap::Parser;
+use lib::{delete_file, get_device_friendly_name, with_device, DeviceArgs};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(flatten)]
+    device: DeviceArgs,
+
+    /// Paths of the files to delete
+    #[arg(required = true)]
+    paths: Vec<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let exit_code = with_device(&args.device, |device| {
+        println!("Connected to {}", get_device_friendly_name(device));
+
+        let mut exit_code = 0;
+        for path in &args.paths {
+            if let Err(e) = delete_file(device, path) {
+                eprintln!("{}", e);
+                if exit_code == 0 {
+                    exit_code = e.exit_code();
+                }
+            }
+        }
+        exit_code
+    })
+    .unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        e.exit_code()
+    });
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+}
@@ -0,0 +1,721 @@
+//! End-to-end tests of the device tools against a fake device filesystem.
+//!
+//! The tools are found through the `UPLOAD_TOOL`, `DOWNLOAD_TOOL`,
+//! `DELETE_TOOL`, `LS_TOOL`, `TAIL_TOOL`, `IDENTIFY_TOOL` and
+//! `INTERPRET_CRASH_LOG_TOOL` environment variables.
+//! Those that talk to a device are run with `--device-root` pointing at a
+//! local directory standing in for the filesystem of a watch.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A fake device tree and a local directory to transfer files to and from.
+struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let base = env::var_os("TEST_TMPDIR").map_or_else(env::temp_dir, PathBuf::from);
+        let dir = base.join(format!(
+            "device_test-{}-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("device/GARMIN/APPS/LOGS")).unwrap();
+        fs::create_dir_all(dir.join("local")).unwrap();
+        Self { dir }
+    }
+
+    /// Returns the path of `path` in the device tree.
+    fn device_path(&self, path: &str) -> PathBuf {
+        self.dir.join("device").join(path.trim_start_matches('/'))
+    }
+
+    /// Returns the path of `name` in the local directory.
+    fn local_path(&self, name: &str) -> PathBuf {
+        self.dir.join("local").join(name)
+    }
+
+    /// Writes `contents` to the device file at `path`.
+    fn write_device_file(&self, path: &str, contents: &str) {
+        let path = self.device_path(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// Writes `contents` to the local file `name`, returning its path.
+    fn write_local_file(&self, name: &str, contents: &str) -> String {
+        let path = self.local_path(name);
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Runs the tool named by the environment variable `tool` against the
+    /// device tree with `args`.
+    fn run(&self, tool: &str, args: &[&str]) -> Output {
+        self.command(tool, args).output().unwrap()
+    }
+
+    /// Returns the command that [`Fixture::run`] runs.
+    fn command(&self, tool: &str, args: &[&str]) -> Command {
+        let mut command = tool_command(tool);
+        command
+            .arg("--device-root")
+            .arg(self.dir.join("device"))
+            .arg("--wait-for")
+            .arg("0")
+            .args(args);
+        command
+    }
+}
+
+/// Returns a command running the tool named by the environment variable `tool`.
+fn tool_command(tool: &str) -> Command {
+    Command::new(env::var_os(tool).unwrap_or_else(|| panic!("{} is not set", tool)))
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Asserts that the tool exited with `code`, showing its output otherwise.
+fn assert_exit_code(output: &Output, code: i32) {
+    assert_eq!(
+        output.status.code(),
+        Some(code),
+        "stdout:\n{}\nstderr:\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn upload_copies_file_into_device_tree() {
+    let fixture = Fixture::new("upload");
+    let src = fixture.write_local_file("APP.PRG", "new");
+
+    let output = fixture.run("UPLOAD_TOOL", &[&src, "/GARMIN/APPS/APP.PRG"]);
+
+    assert_exit_code(&output, 0);
+    assert_eq!(read(&fixture.device_path("/GARMIN/APPS/APP.PRG")), "new");
+}
+
+#[test]
+fn soft_fail_upload_keeps_existing_file() {
+    let fixture = Fixture::new("upload_soft_fail");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "");
+    fixture.write_device_file("/GARMIN/APPS/APP.PRG", "old");
+    let log = fixture.write_local_file("APP.TXT", "placeholder");
+    let prg = fixture.write_local_file("OTHER.PRG", "new");
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &[
+            &log,
+            "?/GARMIN/APPS/LOGS/APP.TXT",
+            &prg,
+            "?/GARMIN/APPS/OTHER.PRG",
+        ],
+    );
+
+    assert_exit_code(&output, 0);
+    assert_eq!(read(&fixture.device_path("/GARMIN/APPS/LOGS/APP.TXT")), "");
+    assert_eq!(read(&fixture.device_path("/GARMIN/APPS/OTHER.PRG")), "new");
+}
+
+#[test]
+fn upload_to_missing_folder_fails_with_exit_code_5() {
+    let fixture = Fixture::new("upload_missing_folder");
+    let src = fixture.write_local_file("APP.PRG", "new");
+
+    for dst in ["/GARMIN/MISSING/APP.PRG", "?/GARMIN/MISSING/APP.PRG"] {
+        let output = fixture.run("UPLOAD_TOOL", &[&src, dst]);
+
+        assert_exit_code(&output, 5);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Folder 'MISSING' not found"));
+    }
+}
+
+#[test]
+fn download_copies_file_from_device_tree() {
+    let fixture = Fixture::new("download");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "log output\n");
+    let dst = fixture.local_path("APP.TXT");
+
+    let output = fixture.run(
+        "DOWNLOAD_TOOL",
+        &["/GARMIN/APPS/LOGS/APP.TXT", &dst.to_string_lossy()],
+    );
+
+    assert_exit_code(&output, 0);
+    assert_eq!(read(&dst), "log output\n");
+}
+
+#[test]
+fn soft_fail_download_skips_missing_file() {
+    let fixture = Fixture::new("download_soft_fail");
+    let dst = fixture.local_path("APP.TXT");
+
+    let output = fixture.run(
+        "DOWNLOAD_TOOL",
+        &["?/GARMIN/APPS/LOGS/APP.TXT", &dst.to_string_lossy()],
+    );
+
+    assert_exit_code(&output, 0);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("skipping download"));
+    assert!(!dst.exists());
+}
+
+#[test]
+fn download_of_missing_file_fails_with_exit_code_6() {
+    let fixture = Fixture::new("download_missing_file");
+    let dst = fixture.local_path("APP.TXT");
+
+    let output = fixture.run(
+        "DOWNLOAD_TOOL",
+        &["/GARMIN/APPS/LOGS/APP.TXT", &dst.to_string_lossy()],
+    );
+
+    assert_exit_code(&output, 6);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("File 'APP.TXT' not found"));
+    assert!(!dst.exists());
+}
+
+#[test]
+fn download_from_missing_folder_fails_with_exit_code_5() {
+    let fixture = Fixture::new("download_missing_folder");
+    let dst = fixture.local_path("APP.TXT");
+
+    let output = fixture.run(
+        "DOWNLOAD_TOOL",
+        &["/GARMIN/MISSING/APP.TXT", &dst.to_string_lossy()],
+    );
+
+    assert_exit_code(&output, 5);
+}
+
+#[test]
+fn delete_removes_app_files_from_device_tree() {
+    let fixture = Fixture::new("delete");
+    fixture.write_device_file("/GARMIN/APPS/APP.PRG", "prg");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "log");
+    fixture.write_device_file("/GARMIN/APPS/OTHER.PRG", "other");
+
+    // As run by ciq_uninstall_app, which soft-fails on files not present.
+    let output = fixture.run(
+        "DELETE_TOOL",
+        &[
+            "?/GARMIN/Apps/APP.prg",
+            "?/GARMIN/Apps/LOGS/APP.TXT",
+            "?/GARMIN/Apps/LOGS/APP.PRF",
+            "?/GARMIN/Apps/SETTINGS/APP.SET",
+        ],
+    );
+
+    assert_exit_code(&output, 0);
+    assert!(!fixture.device_path("/GARMIN/APPS/APP.PRG").exists());
+    assert!(!fixture.device_path("/GARMIN/APPS/LOGS/APP.TXT").exists());
+    assert_eq!(read(&fixture.device_path("/GARMIN/APPS/OTHER.PRG")), "other");
+}
+
+#[test]
+fn delete_of_missing_file_fails_with_exit_code_6() {
+    let fixture = Fixture::new("delete_missing_file");
+    fixture.write_device_file("/GARMIN/APPS/APP.PRG", "prg");
+
+    let output = fixture.run(
+        "DELETE_TOOL",
+        &["/GARMIN/APPS/MISSING.PRG", "/GARMIN/APPS/APP.PRG"],
+    );
+
+    assert_exit_code(&output, 6);
+    // Later paths are still deleted.
+    assert!(!fixture.device_path("/GARMIN/APPS/APP.PRG").exists());
+}
+
+#[test]
+fn later_file_pairs_run_after_a_failure() {
+    let fixture = Fixture::new("download_after_failure");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "log output\n");
+    let missing = fixture.local_path("MISSING.TXT");
+    let dst = fixture.local_path("APP.TXT");
+
+    let output = fixture.run(
+        "DOWNLOAD_TOOL",
+        &[
+            "/GARMIN/APPS/LOGS/MISSING.TXT",
+            &missing.to_string_lossy(),
+            "/GARMIN/APPS/LOGS/APP.TXT",
+            &dst.to_string_lossy(),
+        ],
+    );
+
+    assert_exit_code(&output, 6);
+    assert_eq!(read(&dst), "log output\n");
+}
+
+#[test]
+fn mkdirs_upload_creates_missing_folders() {
+    let fixture = Fixture::new("upload_mkdirs");
+    let src = fixture.write_local_file("APP.TXT", "");
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &["--mkdirs", &src, "/GARMIN/APPS/NEW/LOGS/APP.TXT"],
+    );
+
+    assert_exit_code(&output, 0);
+    assert!(fixture
+        .device_path("/GARMIN/APPS/NEW/LOGS/APP.TXT")
+        .is_file());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Created folder \"/GARMIN/APPS/NEW\""));
+    assert!(stdout.contains("Created folder \"/GARMIN/APPS/NEW/LOGS\""));
+}
+
+#[test]
+fn mkdirs_upload_reuses_folders_differing_in_case() {
+    let fixture = Fixture::new("upload_mkdirs_case");
+    let src = fixture.write_local_file("APP.TXT", "");
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &["--mkdirs", &src, "/garmin/Apps/LOGS/APP.TXT"],
+    );
+
+    assert_exit_code(&output, 0);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Created folder"));
+    assert!(fixture.device_path("/GARMIN/APPS/LOGS/APP.TXT").is_file());
+    let garmin: Vec<_> = fs::read_dir(fixture.device_path("/GARMIN"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(garmin, ["APPS"]);
+}
+
+#[test]
+fn sync_upload_skips_unchanged_files_and_replaces_changed_ones() {
+    let fixture = Fixture::new("upload_sync");
+    fixture.write_device_file("/GARMIN/APPS/SAME.PRG", "same");
+    fixture.write_device_file("/GARMIN/APPS/GROWN.PRG", "old");
+    let same = fixture.write_local_file("SAME.PRG", "same");
+    let grown = fixture.write_local_file("GROWN.PRG", "longer");
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &[
+            "--sync",
+            &same,
+            "/GARMIN/APPS/SAME.PRG",
+            &grown,
+            "/GARMIN/APPS/GROWN.PRG",
+        ],
+    );
+
+    assert_exit_code(&output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("File \"/GARMIN/APPS/SAME.PRG\" unchanged on device"));
+    assert!(stdout.contains("to \"/GARMIN/APPS/GROWN.PRG\""));
+    assert_eq!(
+        read(&fixture.device_path("/GARMIN/APPS/GROWN.PRG")),
+        "longer"
+    );
+}
+
+#[test]
+fn sync_upload_compares_contents_up_to_limit() {
+    let fixture = Fixture::new("upload_sync_compare");
+    fixture.write_device_file("/GARMIN/APPS/SETTINGS/APP.SET", "hello");
+    let src = fixture.write_local_file("APP.SET", "HELLO");
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &["--sync", &src, "/GARMIN/APPS/SETTINGS/APP.SET"],
+    );
+    assert_exit_code(&output, 0);
+    assert_eq!(
+        read(&fixture.device_path("/GARMIN/APPS/SETTINGS/APP.SET")),
+        "hello"
+    );
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &[
+            "--sync",
+            "--compare-up-to",
+            "1024",
+            &src,
+            "/GARMIN/APPS/SETTINGS/APP.SET",
+        ],
+    );
+    assert_exit_code(&output, 0);
+    assert_eq!(
+        read(&fixture.device_path("/GARMIN/APPS/SETTINGS/APP.SET")),
+        "HELLO"
+    );
+}
+
+const CRASH_LOG: &str = r#"---
+Error: Unhandled Exception
+Details: "Failed invoking <symbol>"
+Time: 2024-03-01T12:34:56Z
+Filename: MYAPP
+Appname: My App
+Stack:
+  - pc: 0x10000c9a
+    Function: onUpdate
+    File: 'source/MyView.mc'
+    Line: 25
+  - pc: "268438290"
+---
+Error: Out Of Memory Error
+Filename: OTHER
+Callstack:
+  - pc: 0x10000010
+"#;
+
+#[test]
+fn interprets_crash_log_entries() {
+    let fixture = Fixture::new("crash_log");
+    let log = fixture.write_local_file("CIQ_LOG.YML", CRASH_LOG);
+
+    let output = tool_command("INTERPRET_CRASH_LOG_TOOL")
+        .arg(&log)
+        .output()
+        .unwrap();
+
+    assert_exit_code(&output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().map(str::trim_end).collect();
+    assert_eq!(lines[0], "Crash 1: Unhandled Exception");
+    assert_eq!(lines[1], "  App:     My App (MYAPP)");
+    assert_eq!(lines[2], "  Details: Failed invoking <symbol>");
+    assert_eq!(lines[3], "  Time:    2024-03-01T12:34:56Z");
+    assert!(lines[5].starts_with("    #0  0x10000c9a onUpdate"));
+    assert!(lines[5].ends_with("source/MyView.mc:25"));
+    assert_eq!(lines[6], "    #1  0x10000b12");
+    assert!(stdout.contains("Crash 2: Out Of Memory Error"));
+    assert!(stdout.contains("    #0  0x10000010"));
+}
+
+#[test]
+fn interprets_only_crashes_of_the_given_app() {
+    let fixture = Fixture::new("crash_log_app");
+    let log = fixture.write_local_file("CIQ_LOG.YML", CRASH_LOG);
+
+    let output = tool_command("INTERPRET_CRASH_LOG_TOOL")
+        .args([log.as_str(), "--app", "other"])
+        .output()
+        .unwrap();
+
+    assert_exit_code(&output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("Crash 1: Out Of Memory Error"));
+    assert!(!stdout.contains("Unhandled Exception"));
+
+    let output = tool_command("INTERPRET_CRASH_LOG_TOOL")
+        .args([log.as_str(), "--app", "missing"])
+        .output()
+        .unwrap();
+
+    assert_exit_code(&output, 0);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "No crashes found for app \"missing\" (2 for other apps)."
+    );
+}
+
+const GARMIN_DEVICE_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Device xmlns="http://www.garmin.com/xmlschemas/GarminDevice/v2">
+  <Model>
+    <PartNumber>006-B3906-01</PartNumber>
+    <SoftwareVersion>1520</SoftwareVersion>
+    <Description>fenix 7</Description>
+  </Model>
+</Device>
+"#;
+
+const DEVICES_JSON: &str = r#"{
+  "fenix7": {"compiler": {
+    "hardwarePartNumber": "006-B3906-00",
+    "partNumbers": [{"number": "006-B3906-00"}, {"number": "006-B4341-02"}]
+  }},
+  "fenix7s": {"compiler": {"hardwarePartNumber": "006-B3905-00"}},
+  "fenix7pro": {"compiler": {"hardwarePartNumber": "006-B3906-00"}}
+}"#;
+
+#[test]
+fn identify_maps_part_number_to_device_ids() {
+    let fixture = Fixture::new("identify");
+    fixture.write_device_file("/GARMIN/GarminDevice.xml", GARMIN_DEVICE_XML);
+    let devices_json = fixture.write_local_file("devices.json", DEVICES_JSON);
+
+    let output = fixture.run("IDENTIFY_TOOL", &["--devices-json", &devices_json]);
+
+    assert_exit_code(&output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  Description: fenix 7\n"));
+    assert!(stdout.contains("  Part number: 006-B3906-01\n"));
+    assert!(stdout.contains("  Software version: 1520\n"));
+    assert!(stdout.contains("  Device IDs: fenix7, fenix7pro\n"));
+}
+
+#[test]
+fn identify_maps_regional_part_number_to_device_ids() {
+    let fixture = Fixture::new("identify_regional");
+    fixture.write_device_file(
+        "/GARMIN/GarminDevice.xml",
+        &GARMIN_DEVICE_XML.replace("006-B3906-01", "006-B4341-02"),
+    );
+    let devices_json = fixture.write_local_file("devices.json", DEVICES_JSON);
+
+    let output = fixture.run("IDENTIFY_TOOL", &["--devices-json", &devices_json]);
+
+    assert_exit_code(&output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  Part number: 006-B4341-02\n"));
+    assert!(stdout.contains("  Device IDs: fenix7\n"));
+
+    // The device check refuses builds for other models of such a device too.
+    let src = fixture.write_local_file("APP.PRG", "new");
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &[
+            "--expect-device",
+            "fenix7pro",
+            "--devices-json",
+            &devices_json,
+            &src,
+            "/GARMIN/APPS/APP.PRG",
+        ],
+    );
+    assert_exit_code(&output, 8);
+    assert!(!fixture.device_path("/GARMIN/APPS/APP.PRG").exists());
+}
+
+#[test]
+fn identify_without_garmin_device_xml_reports_unknown_ids() {
+    let fixture = Fixture::new("identify_unknown");
+    let devices_json = fixture.write_local_file("devices.json", DEVICES_JSON);
+
+    let output = fixture.run("IDENTIFY_TOOL", &["--devices-json", &devices_json]);
+
+    assert_exit_code(&output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  Part number: <unknown>\n"));
+    assert!(stdout.contains("  Device IDs: <unknown>\n"));
+}
+
+#[test]
+fn upload_refuses_device_of_another_model_with_exit_code_8() {
+    let fixture = Fixture::new("upload_wrong_device");
+    fixture.write_device_file("/GARMIN/GarminDevice.xml", GARMIN_DEVICE_XML);
+    let devices_json = fixture.write_local_file("devices.json", DEVICES_JSON);
+    let src = fixture.write_local_file("APP.PRG", "new");
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &[
+            "--expect-device",
+            "fenix7s",
+            "--devices-json",
+            &devices_json,
+            &src,
+            "/GARMIN/APPS/APP.PRG",
+        ],
+    );
+    assert_exit_code(&output, 8);
+    assert!(!fixture.device_path("/GARMIN/APPS/APP.PRG").exists());
+
+    let output = fixture.run(
+        "UPLOAD_TOOL",
+        &[
+            "--expect-device",
+            "fenix7pro",
+            "--devices-json",
+            &devices_json,
+            &src,
+            "/GARMIN/APPS/APP.PRG",
+        ],
+    );
+    assert_exit_code(&output, 0);
+    assert!(fixture.device_path("/GARMIN/APPS/APP.PRG").exists());
+}
+
+#[test]
+fn wildcard_download_mirrors_matching_files() {
+    let fixture = Fixture::new("download_wildcard");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "app");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.PRF", "prf");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/OTHER.TXT", "other");
+    fixture.write_device_file("/GARMIN/APPS/DATA/APP.STR", "data");
+    fixture.write_device_file("/GARMIN/APPS/DATA/SUB/APP.IDX", "index");
+    let logs = fixture.local_path("logs");
+    let data = fixture.local_path("data");
+    let apps = fixture.local_path("apps");
+
+    let output = fixture.run(
+        "DOWNLOAD_TOOL",
+        &[
+            "/GARMIN/APPS/LOGS/*.TXT",
+            &logs.to_string_lossy(),
+            "/GARMIN/APPS/DATA/**",
+            &data.to_string_lossy(),
+            "/GARMIN/*/*/APP.???",
+            &apps.to_string_lossy(),
+        ],
+    );
+
+    assert_exit_code(&output, 0);
+    assert_eq!(read(&logs.join("APP.TXT")), "app");
+    assert_eq!(read(&logs.join("OTHER.TXT")), "other");
+    assert!(!logs.join("APP.PRF").exists());
+    assert_eq!(read(&data.join("APP.STR")), "data");
+    assert_eq!(read(&data.join("SUB/APP.IDX")), "index");
+    assert_eq!(read(&apps.join("APPS/LOGS/APP.TXT")), "app");
+    assert_eq!(read(&apps.join("APPS/LOGS/APP.PRF")), "prf");
+    assert_eq!(read(&apps.join("APPS/DATA/APP.STR")), "data");
+    assert!(!apps.join("APPS/DATA/SUB").exists());
+}
+
+#[test]
+fn wildcard_download_without_matches() {
+    let fixture = Fixture::new("download_wildcard_none");
+    let dst = fixture.local_path("logs");
+
+    let output = fixture.run(
+        "DOWNLOAD_TOOL",
+        &["/GARMIN/APPS/LOGS/*.TXT", &dst.to_string_lossy()],
+    );
+    assert_exit_code(&output, 6);
+
+    for pattern in ["?/GARMIN/APPS/LOGS/*.TXT", "?/GARMIN/MISSING/*"] {
+        let output = fixture.run("DOWNLOAD_TOOL", &[pattern, &dst.to_string_lossy()]);
+        assert_exit_code(&output, 0);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("skipping download"));
+    }
+}
+
+#[test]
+fn ls_lists_folders_recursively() {
+    let fixture = Fixture::new("ls");
+    fixture.write_device_file("/GARMIN/APPS/APP.PRG", "prg");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "app log");
+
+    let output = fixture.run("LS_TOOL", &["--recursive", "/GARMIN/Apps"]);
+
+    assert_exit_code(&output, 0);
+    // Each file is followed by its size and modification date.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let listing: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().take(3).collect())
+        .collect();
+    assert_eq!(
+        listing,
+        [
+            vec!["/GARMIN/Apps:"],
+            vec!["APP.PRG", "3", "B"],
+            vec!["LOGS/"],
+            vec!["APP.TXT", "7", "B"],
+        ]
+    );
+}
+
+#[test]
+fn ls_prints_json_listing() {
+    let fixture = Fixture::new("ls_json");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "app log");
+
+    let output = fixture.run("LS_TOOL", &["--json", "/GARMIN/APPS"]);
+
+    assert_exit_code(&output, 0);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(r#""path": "/GARMIN/APPS/LOGS""#), "{}", stdout);
+    assert!(stdout.contains(r#""type": "folder""#), "{}", stdout);
+    assert!(!stdout.contains("APP.TXT"), "{}", stdout);
+}
+
+#[test]
+fn ls_of_missing_folder_fails_with_exit_code_5() {
+    let fixture = Fixture::new("ls_missing_folder");
+
+    let output = fixture.run("LS_TOOL", &["/GARMIN/MISSING"]);
+
+    assert_exit_code(&output, 5);
+}
+
+/// Waits up to 10 seconds for `output` to become `expected`.
+fn wait_for_output(output: &Mutex<Vec<u8>>, expected: &str) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let current = String::from_utf8_lossy(&output.lock().unwrap()).into_owned();
+        if current == expected {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "expected output {:?}, got {:?}",
+            expected,
+            current
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn tail_prints_output_appended_to_a_growing_file() {
+    let fixture = Fixture::new("tail");
+    fixture.write_device_file("/GARMIN/APPS/LOGS/APP.TXT", "first\n");
+
+    let mut child = fixture
+        .command("TAIL_TOOL", &["--interval", "20", "/GARMIN/APPS/LOGS/APP.TXT"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let reader = {
+        let mut stdout = child.stdout.take().unwrap();
+        let output = Arc::clone(&output);
+        std::thread::spawn(move || {
+            let mut buffer = [0; 256];
+            while let Ok(read @ 1..) = stdout.read(&mut buffer) {
+                output.lock().unwrap().extend_from_slice(&buffer[..read]);
+            }
+        })
+    };
+
+    let append = |text: &str| {
+        let mut log = fs::OpenOptions::new()
+            .append(true)
+            .open(fixture.device_path("/GARMIN/APPS/LOGS/APP.TXT"))
+            .unwrap();
+        log.write_all(text.as_bytes()).unwrap();
+    };
+    wait_for_output(&output, "first\n");
+    append("second\n");
+    wait_for_output(&output, "first\nsecond\n");
+    append("third\n");
+    wait_for_output(&output, "first\nsecond\nthird\n");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    reader.join().unwrap();
+}
@@ -0,0 +1,223 @@
+//! A [`DeviceTransport`] backed by a local directory.
+//!
+//! The directory stands in for the root of a device's filesystem, so a tree
+//! such as `<root>/GARMIN/APPS/LOGS` behaves like the folders of the same
+//! name on a watch. This allows sideload and log retrieval flows to be
+//! exercised without a physical device attached.
+
+use crate::{DeviceError, DeviceTransport, FileEntry, FolderEntry};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct DirectoryDevice {
+    root: PathBuf,
+    /// Paths relative to `root`, indexed by object ID minus one.
+    objects: RefCell<Vec<PathBuf>>,
+}
+
+impl DirectoryDevice {
+    /// Opens the directory at `root` as a device.
+    ///
+    /// Returns `None` if the directory does not exist, mirroring a physical
+    /// device that is not connected.
+    pub fn open(root: &Path) -> Option<Self> {
+        if !root.is_dir() {
+            return None;
+        }
+        Some(DirectoryDevice {
+            root: root.to_path_buf(),
+            objects: RefCell::new(Vec::new()),
+        })
+    }
+
+    fn object_id(&self, relative_path: &Path) -> u32 {
+        let mut objects = self.objects.borrow_mut();
+        let index = match objects.iter().position(|p| p == relative_path) {
+            Some(index) => index,
+            None => {
+                objects.push(relative_path.to_path_buf());
+                objects.len() - 1
+            }
+        };
+        index as u32 + 1
+    }
+
+    fn object_path(&self, id: u32) -> Result<PathBuf, DeviceError> {
+        if id == 0 {
+            return Ok(self.root.clone());
+        }
+        self.objects
+            .borrow()
+            .get(id as usize - 1)
+            .map(|p| self.root.join(p))
+            .ok_or_else(|| DeviceError::Other(format!("Object ID {} not found", id)))
+    }
+
+    fn walk(&self) -> Result<(Vec<FolderEntry>, Vec<FileEntry>), DeviceError> {
+        let mut folders = Vec::new();
+        let mut files = Vec::new();
+        let mut pending = vec![(PathBuf::new(), 0)];
+
+        while let Some((relative_dir, parent_id)) = pending.pop() {
+            let dir = self.root.join(&relative_dir);
+            let read_error = |e: std::io::Error| {
+                DeviceError::transfer_failed(format!(
+                    "Error reading directory '{}': {}",
+                    dir.display(),
+                    e
+                ))
+            };
+            let mut entries = std::fs::read_dir(&dir)
+                .map_err(read_error)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(read_error)?;
+            entries.sort_by_key(|entry| entry.file_name());
+
+            for entry in entries {
+                let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                    continue;
+                };
+                let relative_path = relative_dir.join(&name);
+                let id = self.object_id(&relative_path);
+                let metadata = entry.metadata().map_err(|e| {
+                    DeviceError::transfer_failed(format!(
+                        "Error reading metadata for '{}': {}",
+                        name, e
+                    ))
+                })?;
+
+                if metadata.is_dir() {
+                    folders.push(FolderEntry {
+                        folder_id: id,
+                        parent_id,
+                        name,
+                    });
+                    pending.push((relative_path, id));
+                } else {
+                    let modificationdate = metadata
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map_or(0, |d| d.as_secs() as i64);
+                    files.push(FileEntry {
+                        item_id: id,
+                        parent_id,
+                        filename: name,
+                        filesize: metadata.len(),
+                        modificationdate,
+                    });
+                }
+            }
+        }
+
+        Ok((folders, files))
+    }
+}
+
+impl DeviceTransport for DirectoryDevice {
+    fn friendly_name(&self) -> String {
+        format!("directory \"{}\"", self.root.display())
+    }
+
+    fn folders(&self) -> Result<Vec<FolderEntry>, DeviceError> {
+        Ok(self.walk()?.0)
+    }
+
+    fn files(&self) -> Result<Vec<FileEntry>, DeviceError> {
+        Ok(self.walk()?.1)
+    }
+
+    fn send_file(
+        &self,
+        src: &str,
+        parent_id: u32,
+        filename: &str,
+        _filesize: u64,
+        progress: &mut dyn FnMut(u64, u64) -> bool,
+    ) -> Result<(), DeviceError> {
+        let dst = self.object_path(parent_id)?.join(filename);
+        copy_with_progress(Path::new(src), &dst, progress)
+            .map_err(|e| DeviceError::transfer_failed(format!("Error uploading file: {}", e)))
+    }
+
+    fn create_folder(&self, name: &str, parent_id: u32) -> Result<u32, DeviceError> {
+        let parent = self.object_path(parent_id)?;
+        std::fs::create_dir(parent.join(name)).map_err(|e| {
+            DeviceError::transfer_failed(format!("Error creating folder '{}': {}", name, e))
+        })?;
+        let relative_path = parent
+            .strip_prefix(&self.root)
+            .map_err(|e| DeviceError::Other(e.to_string()))?
+            .join(name);
+        Ok(self.object_id(&relative_path))
+    }
+
+    fn delete_object(&self, object_id: u32) -> Result<(), DeviceError> {
+        if object_id == 0 {
+            return Err(DeviceError::transfer_failed(
+                "Error deleting file: cannot delete the root folder",
+            ));
+        }
+        let path = self.object_path(object_id)?;
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        result.map_err(|e| DeviceError::transfer_failed(format!("Error deleting file: {}", e)))
+    }
+
+    fn get_file(
+        &self,
+        item_id: u32,
+        dst: &str,
+        progress: &mut dyn FnMut(u64, u64) -> bool,
+    ) -> Result<(), DeviceError> {
+        let src = self.object_path(item_id)?;
+        copy_with_progress(&src, Path::new(dst), progress)
+            .map_err(|e| DeviceError::transfer_failed(format!("Error downloading file: {}", e)))
+    }
+}
+
+/// Copies `src` to `dst` in chunks, reporting progress after each chunk.
+///
+/// If `progress` asks for cancellation, the partially written `dst` is removed.
+fn copy_with_progress(
+    src: &Path,
+    dst: &Path,
+    progress: &mut dyn FnMut(u64, u64) -> bool,
+) -> std::io::Result<()> {
+    let mut reader = File::open(src)?;
+    let total = reader.metadata()?.len();
+    let mut writer = File::create(dst)?;
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+    let mut sent = 0;
+
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..n])?;
+        sent += n as u64;
+        if !progress(sent, total) {
+            drop(writer);
+            std::fs::remove_file(dst)?;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "transfer cancelled",
+            ));
+        }
+    }
+
+    if total == 0 {
+        progress(0, 0);
+    }
+
+    Ok(())
+}
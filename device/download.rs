@@ -12,6 +12,21 @@
 //! If the device path is prefixed with `?` (e.g. `?/GARMIN/Apps/my_app.prg`),
 //! the download will "soft-fail": if the file does not exist on the device,
 //! the tool will exit successfully without downloading anything.
+//!
+//! The device path may contain wildcards, in which case every matching file
+//! is downloaded into the local path, treated as a directory. Within a path
+//! segment, `*` matches any run of characters and `?` any single character,
+//! and a `**` segment matches any number of nested folders. Files keep their
+//! path relative to the last folder before the first wildcard, e.g.
+//!   bazel run @rules_ciq//device:download -- "/GARMIN/Apps/LOGS/*.TXT" ./logs
+//! With the `?` prefix, a pattern that matches nothing is skipped.
+//!
+//! Passing `--device-root <dir>` performs the download against a local directory
+//! standing in for the device filesystem, instead of a connected MTP device.
+//!
+//! On failure, the exit status identifies the kind of error, e.g. 4 when the
+//! device is claimed by another process and 6 when a file is missing. See
+//! `error.rs` for the full list.
 
 use lib::{download_file, run_mtp_operation};
 
@@ -0,0 +1,118 @@
+//! Errors reported by device operations.
+//!
+//! Every [`DeviceError`] belongs to a category with its own process exit code,
+//! so that scripts wrapping the tools can, for example, retry when the device
+//! is busy but give up straight away when a file is missing:
+//!
+//! | Exit code | Category                                               |
+//! | --------- | ------------------------------------------------------ |
+//! | 1         | Any other error, e.g. an unreadable local file         |
+//! | 3         | No device found                                        |
+//! | 4         | Device found but busy, e.g. claimed by another process |
+//! | 5         | Folder not found on the device                         |
+//! | 6         | File not found on the device                           |
+//! | 7         | Transfer or other device operation failed              |
+//! | 8         | Connected device is not the expected model             |
+//! | 130       | Cancelled with Ctrl-C                                  |
+//!
+//! Exit code 2 is left to command line parsing errors.
+
+use crate::progress::CANCELLED_EXIT_CODE;
+use std::fmt;
+
+pub const OTHER_EXIT_CODE: i32 = 1;
+pub const NO_DEVICE_EXIT_CODE: i32 = 3;
+pub const DEVICE_BUSY_EXIT_CODE: i32 = 4;
+pub const FOLDER_NOT_FOUND_EXIT_CODE: i32 = 5;
+pub const FILE_NOT_FOUND_EXIT_CODE: i32 = 6;
+pub const TRANSFER_FAILED_EXIT_CODE: i32 = 7;
+pub const WRONG_DEVICE_EXIT_CODE: i32 = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceError {
+    /// No device appeared within the wait time.
+    NoDevice,
+    /// A device was detected but could not be opened.
+    DeviceBusy,
+    /// A folder along a device path does not exist.
+    FolderNotFound {
+        name: String,
+    },
+    /// No file on the device has the given name, or matches the given pattern.
+    FileNotFound {
+        name: String,
+    },
+    /// The device reported an error, with any details from the libmtp error
+    /// stack, oldest first.
+    TransferFailed {
+        message: String,
+        details: Vec<String>,
+    },
+    /// The connected device is not the model a build was compiled for.
+    WrongDevice {
+        expected: String,
+        actual: Vec<String>,
+    },
+    /// The user cancelled the operation with Ctrl-C.
+    Cancelled {
+        message: String,
+    },
+    Other(String),
+}
+
+impl DeviceError {
+    /// Creates a [`DeviceError::TransferFailed`] without further details.
+    pub fn transfer_failed(message: impl Into<String>) -> Self {
+        DeviceError::TransferFailed {
+            message: message.into(),
+            details: Vec::new(),
+        }
+    }
+
+    /// Returns the process exit code for this error's category.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DeviceError::NoDevice => NO_DEVICE_EXIT_CODE,
+            DeviceError::DeviceBusy => DEVICE_BUSY_EXIT_CODE,
+            DeviceError::FolderNotFound { .. } => FOLDER_NOT_FOUND_EXIT_CODE,
+            DeviceError::FileNotFound { .. } => FILE_NOT_FOUND_EXIT_CODE,
+            DeviceError::TransferFailed { .. } => TRANSFER_FAILED_EXIT_CODE,
+            DeviceError::WrongDevice { .. } => WRONG_DEVICE_EXIT_CODE,
+            DeviceError::Cancelled { .. } => CANCELLED_EXIT_CODE,
+            DeviceError::Other(_) => OTHER_EXIT_CODE,
+        }
+    }
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceError::NoDevice => write!(f, "No MTP device found."),
+            DeviceError::DeviceBusy => {
+                write!(
+                    f,
+                    "MTP device found, but already claimed by another process."
+                )
+            }
+            DeviceError::FolderNotFound { name } => write!(f, "Folder '{}' not found", name),
+            DeviceError::FileNotFound { name } => write!(f, "File '{}' not found", name),
+            DeviceError::TransferFailed { message, details } => {
+                write!(f, "{}", message)?;
+                if !details.is_empty() {
+                    write!(f, " ({})", details.join("; "))?;
+                }
+                Ok(())
+            }
+            DeviceError::WrongDevice { expected, actual } => write!(
+                f,
+                "Connected device is {}, but the build is for {}",
+                actual.join(" or "),
+                expected
+            ),
+            DeviceError::Cancelled { message } => write!(f, "{}", message),
+            DeviceError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {}
@@ -0,0 +1,82 @@
+//! Wildcard matching of names, shared by the device tools, which match device
+//! paths one segment at a time, and the simulator, which matches test names.
+
+/// Returns whether `name` matches `pattern`, in which `*` matches any run of
+/// characters and `?` matches any single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was tried at.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_match;
+
+    #[test]
+    fn matches_literal_names_exactly() {
+        assert!(glob_match("APP.TXT", "APP.TXT"));
+        assert!(!glob_match("APP.TXT", "APP.TX"));
+        assert!(!glob_match("APP.TX", "APP.TXT"));
+        assert!(!glob_match("APP.TXT", "app.txt"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "A"));
+    }
+
+    #[test]
+    fn star_matches_any_run_of_characters() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "APP.TXT"));
+        assert!(glob_match("*.TXT", "APP.TXT"));
+        assert!(glob_match("*.TXT", ".TXT"));
+        assert!(!glob_match("*.TXT", "APP.PRF"));
+        assert!(glob_match("APP*", "APP"));
+        assert!(glob_match("*Layout*", "testLayoutOnRound"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+        assert!(glob_match("**", "anything"));
+    }
+
+    #[test]
+    fn star_backtracks_past_partial_matches() {
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("*aab", "aaab"));
+        assert!(glob_match("*.TXT", "A.TXT.TXT"));
+        assert!(!glob_match("*.TXT", "A.TXT.PRF"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob_match("APP?.TXT", "APP1.TXT"));
+        assert!(!glob_match("APP?.TXT", "APP.TXT"));
+        assert!(!glob_match("APP?.TXT", "APP12.TXT"));
+        assert!(glob_match("f?nix", "fēnix"));
+        assert!(glob_match("?*", "A"));
+        assert!(!glob_match("?*", ""));
+    }
+}
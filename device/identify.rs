@@ -0,0 +1,68 @@
+//! Identifies the model of a connected device.
+//!
+//! Usage:
+//!   bazel run @rules_ciq//device:identify -- [--devices-json <path>]
+//!
+//! Prints the model name, serial number and firmware version reported over
+//! MTP, and the description, part number and software version from the
+//! device's `/GARMIN/GarminDevice.xml`. With `--devices-json`, also prints the
+//! Connect IQ device IDs whose hardware part number matches the device, i.e.
+//! the `device_id`s of the `ciq_device_build` targets that can run on it.
+//!
+//! Passing `--device-root <dir>` identifies a local directory standing in for
+//! the device filesystem, instead of a connected MTP device.
+
+use clap::Parser;
+use lib::{get_device_friendly_name, read_identity, with_device, DeviceArgs};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(flatten)]
+    device: DeviceArgs,
+
+    /// Path to the SDK's devices.json, used to map the part number to device IDs
+    #[arg(long, value_name = "PATH")]
+    devices_json: Option<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let result = with_device(&args.device, |device| {
+        let identity = read_identity(device, args.devices_json.as_deref())?;
+        let unknown = || "<unknown>".to_string();
+
+        println!("Name: {}", get_device_friendly_name(device));
+        println!("  Model: {}", identity.model_name);
+        println!("  Serial: {}", identity.serial_number);
+        println!("  Firmware: {}", identity.firmware_version);
+        println!(
+            "  Description: {}",
+            identity.description.unwrap_or_else(unknown)
+        );
+        println!(
+            "  Part number: {}",
+            identity.part_number.unwrap_or_else(unknown)
+        );
+        println!(
+            "  Software version: {}",
+            identity.software_version.unwrap_or_else(unknown)
+        );
+        if args.devices_json.is_some() {
+            if identity.device_ids.is_empty() {
+                println!("  Device IDs: <unknown>");
+            } else {
+                println!("  Device IDs: {}", identity.device_ids.join(", "));
+            }
+        }
+        Ok(())
+    })
+    .and_then(|r| r);
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+}
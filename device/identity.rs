@@ -0,0 +1,200 @@
+//! Identification of the model of a connected device.
+//!
+//! Besides the model name, serial number and firmware version reported over
+//! MTP, every Garmin device describes itself in `/GARMIN/GarminDevice.xml`,
+//! whose part number can be matched against the `hardwarePartNumber` and the
+//! regional `partNumbers` of the Connect IQ devices in the SDK's
+//! `devices.json` to find the device IDs that builds for the device are
+//! compiled with.
+
+use crate::{
+    find_file_entry_in_folder, get_folder_reference, is_cancelled, DeviceError, DeviceTransport,
+};
+use std::collections::HashMap;
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+/// Path of the file in which a device describes itself.
+pub const GARMIN_DEVICE_XML_PATH: &str = "/GARMIN/GarminDevice.xml";
+
+/// What is known about the model of a connected device.
+#[derive(Clone, Debug, Default)]
+pub struct DeviceIdentity {
+    pub model_name: String,
+    pub serial_number: String,
+    pub firmware_version: String,
+    /// `Model/Description` from `GarminDevice.xml`, e.g. `fēnix 7`.
+    pub description: Option<String>,
+    /// `Model/PartNumber` from `GarminDevice.xml`, e.g. `006-B3906-00`.
+    pub part_number: Option<String>,
+    /// `Model/SoftwareVersion` from `GarminDevice.xml`, e.g. `1520`.
+    pub software_version: Option<String>,
+    /// Connect IQ device IDs with a part number matching the device's.
+    pub device_ids: Vec<String>,
+}
+
+/// Reads the identity of `device`, mapping its part number to Connect IQ
+/// device IDs if `devices_json` is given.
+///
+/// A missing `GarminDevice.xml` is not an error: the fields read from it are
+/// left unset, and no device IDs are found.
+pub fn read_identity<T: DeviceTransport + ?Sized>(
+    device: &T,
+    devices_json: Option<&Path>,
+) -> Result<DeviceIdentity, DeviceError> {
+    let mut identity = DeviceIdentity {
+        model_name: device.model_name(),
+        serial_number: device.serial_number(),
+        firmware_version: device.firmware_version(),
+        ..Default::default()
+    };
+
+    match read_device_file(device, GARMIN_DEVICE_XML_PATH) {
+        Ok(contents) => parse_garmin_device_xml(&contents, &mut identity)?,
+        Err(DeviceError::FolderNotFound { .. } | DeviceError::FileNotFound { .. }) => {}
+        Err(e) => return Err(e),
+    }
+
+    if let (Some(part_number), Some(devices_json)) = (&identity.part_number, devices_json) {
+        identity.device_ids = device_ids_for_part_number(devices_json, part_number)?;
+    }
+
+    Ok(identity)
+}
+
+/// Fails with [`DeviceError::WrongDevice`] unless `device` can run builds for
+/// the Connect IQ device `expected`.
+///
+/// Devices whose part number cannot be determined or does not appear in
+/// `devices_json` are assumed to match, with a warning.
+pub fn check_device_id<T: DeviceTransport + ?Sized>(
+    device: &T,
+    expected: &str,
+    devices_json: &Path,
+) -> Result<(), DeviceError> {
+    let identity = read_identity(device, Some(devices_json))?;
+
+    if identity.device_ids.is_empty() {
+        eprintln!(
+            "Warning: could not determine the device ID of {}, assuming it is {}.",
+            device.friendly_name(),
+            expected
+        );
+        return Ok(());
+    }
+
+    if !identity.device_ids.iter().any(|id| id == expected) {
+        return Err(DeviceError::WrongDevice {
+            expected: expected.to_string(),
+            actual: identity.device_ids,
+        });
+    }
+
+    Ok(())
+}
+
+/// Reads the whole of the device file at `path` into memory.
+fn read_device_file<T: DeviceTransport + ?Sized>(
+    device: &T,
+    path: &str,
+) -> Result<Vec<u8>, DeviceError> {
+    let filename = Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(path);
+    let folder_id = get_folder_reference(device, path)?;
+    let entry = find_file_entry_in_folder(device, folder_id, filename)?;
+
+    let temp_path = std::env::temp_dir().join(format!(
+        "ciq-identity-{}-{}",
+        std::process::id(),
+        entry.item_id
+    ));
+    let temp_path_str = temp_path.to_string_lossy();
+    let result = device
+        .get_file(entry.item_id, &temp_path_str, &mut |_, _| !is_cancelled())
+        .and_then(|()| std::fs::read(&temp_path).map_err(|e| DeviceError::Other(e.to_string())));
+    let _ = std::fs::remove_file(&temp_path);
+    result
+}
+
+/// Fills in the fields of `identity` that come from `GarminDevice.xml`.
+fn parse_garmin_device_xml(
+    contents: &[u8],
+    identity: &mut DeviceIdentity,
+) -> Result<(), DeviceError> {
+    let mut path: Vec<String> = Vec::new();
+
+    for event in EventReader::new(contents) {
+        match event {
+            Ok(XmlEvent::StartElement { name, .. }) => path.push(name.local_name),
+            Ok(XmlEvent::EndElement { .. }) => {
+                path.pop();
+            }
+            Ok(XmlEvent::Characters(text)) => {
+                let field = match path.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                    ["Device", "Model", "Description"] => &mut identity.description,
+                    ["Device", "Model", "PartNumber"] => &mut identity.part_number,
+                    ["Device", "Model", "SoftwareVersion"] => &mut identity.software_version,
+                    _ => continue,
+                };
+                *field = Some(text.trim().to_string());
+            }
+            Ok(XmlEvent::EndDocument) => break,
+            Err(e) => {
+                return Err(DeviceError::Other(format!(
+                    "Error parsing {}: {}",
+                    GARMIN_DEVICE_XML_PATH, e
+                )))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the variant suffix of a part number with `00`, so that regional
+/// variants of a device such as `006-B3906-01` match `006-B3906-00`.
+fn normalize_part_number(part_number: &str) -> String {
+    match part_number.rfind('-') {
+        Some(idx) => format!("{}-00", &part_number[..idx]),
+        None => part_number.to_string(),
+    }
+}
+
+/// Returns the IDs of the devices in `devices_json` whose hardware part
+/// number, or one of whose regional part numbers, matches `part_number`,
+/// sorted by name.
+pub fn device_ids_for_part_number(
+    devices_json: &Path,
+    part_number: &str,
+) -> Result<Vec<String>, DeviceError> {
+    let contents = std::fs::read_to_string(devices_json).map_err(|e| {
+        DeviceError::Other(format!("Error reading '{}': {}", devices_json.display(), e))
+    })?;
+    let devices: HashMap<String, serde_json::Value> =
+        serde_json::from_str(&contents).map_err(|e| {
+            DeviceError::Other(format!("Error parsing '{}': {}", devices_json.display(), e))
+        })?;
+
+    let normalized = normalize_part_number(part_number);
+    let mut device_ids: Vec<String> = devices
+        .into_iter()
+        .filter(|(_, metadata)| {
+            let compiler = &metadata["compiler"];
+            let regional = compiler["partNumbers"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|part| &part["number"]);
+            std::iter::once(&compiler["hardwarePartNumber"])
+                .chain(regional)
+                .filter_map(serde_json::Value::as_str)
+                .any(|number| normalize_part_number(number) == normalized)
+        })
+        .map(|(device_id, _)| device_id)
+        .collect();
+    device_ids.sort();
+    Ok(device_ids)
+}
@@ -0,0 +1,245 @@
+//! Interprets a Connect IQ crash log (`CIQ_LOG.YML`).
+//!
+//! Usage:
+//!   bazel run @rules_ciq//device:interpret_crash_log -- <path_to_log> [--debug-xml <path_to_debug_xml>] [--app <name>]
+//!
+//! When an app crashes, the device appends an entry to `/GARMIN/Apps/LOGS/CIQ_LOG.YML`
+//! describing the error and the program counters (PCs) of the call stack. This
+//! tool parses those entries and prints them as stack traces, resolving PCs to
+//! functions and source lines using the associated debug XML file if provided.
+//!
+//! Entries are YAML documents separated by `---`, for example:
+//!
+//! ```yaml
+//! ---
+//! Error: Unhandled Exception
+//! Details: "Failed invoking <symbol>"
+//! Time: 2024-03-01T12:34:56Z
+//! Filename: MYAPP
+//! Appname: My App
+//! Stack:
+//!   - pc: 0x10000c9a
+//!   - pc: 0x10000b12
+//! ```
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use debug_xml::{parse_debug_xml, DebugInfo};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the CIQ_LOG.YML crash log file
+    file: String,
+
+    /// Path to the .prg.debug.xml file for symbol resolution (optional)
+    #[arg(short, long)]
+    debug_xml: Option<String>,
+
+    /// Only show crashes of the app with this file name or app name
+    #[arg(long)]
+    app: Option<String>,
+}
+
+#[derive(Default, Debug)]
+struct StackFrame {
+    pc: Option<u32>,
+    function: Option<String>,
+    file: Option<String>,
+    line: Option<String>,
+}
+
+#[derive(Default, Debug)]
+struct CrashEntry {
+    error: Option<String>,
+    details: Option<String>,
+    time: Option<String>,
+    filename: Option<String>,
+    app_name: Option<String>,
+    stack: Vec<StackFrame>,
+}
+
+impl CrashEntry {
+    fn matches_app(&self, app: &str) -> bool {
+        [&self.filename, &self.app_name]
+            .into_iter()
+            .flatten()
+            .any(|name| name.eq_ignore_ascii_case(app))
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let debug_info = if let Some(xml_path) = &args.debug_xml {
+        Some(parse_debug_xml(xml_path)?)
+    } else {
+        None
+    };
+
+    let text = std::fs::read_to_string(&args.file).context("Failed to read crash log")?;
+    let entries = parse_crash_log(&text);
+
+    let matching: Vec<&CrashEntry> = entries
+        .iter()
+        .filter(|entry| args.app.as_ref().is_none_or(|app| entry.matches_app(app)))
+        .collect();
+
+    if matching.is_empty() {
+        match &args.app {
+            Some(app) => println!(
+                "No crashes found for app \"{}\" ({} for other apps).",
+                app,
+                entries.len()
+            ),
+            None => println!("No crashes found."),
+        }
+        return Ok(());
+    }
+
+    for (index, entry) in matching.iter().enumerate() {
+        print_entry(index + 1, entry, debug_info.as_ref());
+    }
+
+    Ok(())
+}
+
+/// Removes surrounding quotes from a YAML scalar.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+fn parse_pc(value: &str) -> Option<u32> {
+    let value = unquote(value);
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Parses every entry in a crash log.
+///
+/// Only the subset of YAML used by the device is understood: top-level
+/// `Key: value` pairs, and a `Stack` list whose items are `Key: value` maps.
+/// Keys are matched case-insensitively and unknown keys are ignored.
+fn parse_crash_log(text: &str) -> Vec<CrashEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<CrashEntry> = None;
+    let mut in_stack = false;
+
+    for raw_line in text.lines() {
+        let line = raw_line.trim_end();
+        if line.starts_with("---") {
+            entries.extend(current.take());
+            in_stack = false;
+            continue;
+        }
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let entry = current.get_or_insert_with(CrashEntry::default);
+        let indented = line.starts_with(char::is_whitespace) || line.starts_with('-');
+        let mut content = line.trim_start();
+
+        if in_stack && indented {
+            if let Some(item) = content.strip_prefix('-') {
+                entry.stack.push(StackFrame::default());
+                content = item.trim_start();
+            }
+            let (Some(frame), Some((key, value))) =
+                (entry.stack.last_mut(), content.split_once(':'))
+            else {
+                continue;
+            };
+            match key.trim().to_ascii_lowercase().as_str() {
+                "pc" => frame.pc = parse_pc(value),
+                "function" | "symbol" => frame.function = Some(unquote(value)),
+                "file" => frame.file = Some(unquote(value)),
+                "line" => frame.line = Some(unquote(value)),
+                _ => {}
+            }
+            continue;
+        }
+
+        in_stack = false;
+        let Some((key, value)) = content.split_once(':') else {
+            continue;
+        };
+        let value = Some(unquote(value)).filter(|v| !v.is_empty());
+        match key.trim().to_ascii_lowercase().as_str() {
+            "error" => entry.error = value,
+            "details" => entry.details = value,
+            "time" => entry.time = value,
+            "filename" => entry.filename = value,
+            "appname" => entry.app_name = value,
+            "stack" | "callstack" => in_stack = true,
+            _ => {}
+        }
+    }
+
+    entries.extend(current);
+    entries
+}
+
+fn print_entry(number: usize, entry: &CrashEntry, debug_info: Option<&DebugInfo>) {
+    println!(
+        "Crash {}: {}",
+        number,
+        entry.error.as_deref().unwrap_or("Unknown error")
+    );
+    match (&entry.app_name, &entry.filename) {
+        (Some(app_name), Some(filename)) => println!("  App:     {} ({})", app_name, filename),
+        (Some(name), None) | (None, Some(name)) => println!("  App:     {}", name),
+        (None, None) => {}
+    }
+    if let Some(details) = &entry.details {
+        println!("  Details: {}", details);
+    }
+    if let Some(time) = &entry.time {
+        println!("  Time:    {}", time);
+    }
+
+    println!("  Stack:");
+    if entry.stack.is_empty() {
+        println!("    <No stack trace>");
+    }
+    for (depth, frame) in entry.stack.iter().enumerate() {
+        let pc = frame
+            .pc
+            .map_or("?".to_string(), |pc| format!("0x{:08x}", pc));
+        let location = frame
+            .pc
+            .and_then(|pc| debug_info.and_then(|d| d.locate(pc as i32)));
+
+        let (function, source) = match location {
+            Some(location) => (
+                location.symbol.clone(),
+                format!("{}:{}", location.file, location.line),
+            ),
+            None => (
+                frame.function.clone().unwrap_or_default(),
+                match (&frame.file, &frame.line) {
+                    (Some(file), Some(line)) => format!("{}:{}", file, line),
+                    (Some(file), None) => file.clone(),
+                    _ => String::new(),
+                },
+            ),
+        };
+
+        println!("    #{:<2} {} {:<40} {}", depth, pc, function, source);
+    }
+    println!();
+}
@@ -8,12 +8,12 @@
 
 use anyhow::{Context, Result};
 use clap::Parser;
+use debug_xml::{parse_debug_xml, SourceLocation};
 use prost::bytes::Buf; // Use Buf trait for advancing through the slice
 use prost::Message;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use xml::reader::{EventReader, XmlEvent};
+use std::io::Read;
 
 // Manual Protobuf Definition
 #[derive(Clone, PartialEq, Message)]
@@ -59,13 +59,6 @@ struct Args {
     show_callstacks: bool,
 }
 
-#[derive(Clone, Debug)]
-struct SourceLocation {
-    file: String,
-    line: i32,
-    symbol: String,
-}
-
 struct FunctionStats {
     name: String,
     call_count: u64,
@@ -80,11 +73,6 @@ struct StackFrame {
     children_time: i64,
 }
 
-struct DebugInfo {
-    pc_to_name: HashMap<i32, String>,
-    pc_to_source: HashMap<i32, SourceLocation>,
-}
-
 fn main() -> Result<()> {
     let args = Args::parse();
 
@@ -286,90 +274,3 @@ fn print_report(
         }
     }
 }
-
-fn parse_debug_xml(path: &str) -> Result<DebugInfo> {
-    let file = File::open(path).context("Failed to open debug XML")?;
-    let file = BufReader::new(file);
-    let parser = EventReader::new(file);
-
-    let mut pc_to_name = HashMap::new();
-    let mut pc_to_source = HashMap::new();
-
-    for e in parser {
-        match e {
-            Ok(XmlEvent::StartElement {
-                name, attributes, ..
-            }) => {
-                if name.local_name == "functionEntry" {
-                    let mut func_name = String::new();
-                    let mut parent_name = String::new();
-                    let mut start_pc = -1;
-
-                    for attr in attributes {
-                        match attr.name.local_name.as_str() {
-                            "name" => func_name = attr.value,
-                            "parent" => parent_name = attr.value,
-                            "startPc" => start_pc = attr.value.parse().unwrap_or(-1),
-                            _ => {}
-                        }
-                    }
-
-                    if start_pc != -1 {
-                        let full_name = if !parent_name.is_empty() {
-                            format!("{}.{}", parent_name, func_name)
-                        } else {
-                            func_name
-                        };
-                        pc_to_name.insert(start_pc, full_name);
-                    }
-                } else if name.local_name == "entry" {
-                    // Parse pcToLineNum entries
-                    // <entry filename="..." id="1" lineNum="12" parent="globals/SampleView" pc="268435460" symbol="<init>"/>
-                    let mut filename = String::new();
-                    let mut line_num = -1;
-                    let mut pc = -1;
-                    let mut symbol = String::new();
-                    let mut parent = String::new();
-
-                    for attr in attributes {
-                        match attr.name.local_name.as_str() {
-                            "filename" => filename = attr.value,
-                            "lineNum" => line_num = attr.value.parse().unwrap_or(-1),
-                            "pc" => pc = attr.value.parse().unwrap_or(-1),
-                            "symbol" => symbol = attr.value,
-                            "parent" => parent = attr.value,
-                            _ => {}
-                        }
-                    }
-
-                    if pc != -1 {
-                        // Clean up parent (e.g. "globals/SampleView" -> "SampleView")
-                        let clean_parent = parent.replace("globals/", "");
-                        let full_name = if !clean_parent.is_empty() {
-                            format!("{}.{}", clean_parent, symbol)
-                        } else {
-                            symbol.clone()
-                        };
-
-                        pc_to_source.insert(
-                            pc,
-                            SourceLocation {
-                                file: filename,
-                                line: line_num,
-                                symbol: full_name,
-                            },
-                        );
-                    }
-                }
-            }
-            Ok(XmlEvent::EndDocument) => break,
-            Err(e) => return Err(e.into()),
-            _ => {}
-        }
-    }
-
-    Ok(DebugInfo {
-        pc_to_name,
-        pc_to_source,
-    })
-}
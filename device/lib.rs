@@ -2,29 +2,151 @@
 //!
 //! This library provides a Rust interface to `libmtp` for interacting with MTP devices.
 //! It supports listing files, uploading files, and downloading files.
+//!
+//! Device access goes through the [`DeviceTransport`] trait, which is
+//! implemented by [`MtpDevice`] for physical devices and by
+//! [`DirectoryDevice`] for a device filesystem emulated by a local directory.
+//!
+//! Operations fail with a [`DeviceError`], whose category determines the exit
+//! code of the tools.
+
+mod directory_device;
+mod error;
+mod identity;
+mod progress;
 
-use clap::Parser;
+pub use directory_device::DirectoryDevice;
+pub use error::DeviceError;
+pub use identity::{check_device_id, device_ids_for_part_number, read_identity, DeviceIdentity};
+pub use progress::{format_bytes, install_cancellation_handler, is_cancelled, TransferProgress};
+
+use clap::{Args, Parser};
+use glob_match::glob_match;
 use libc;
 use std::ffi::c_void;
 use std::ffi::CStr;
 use std::ffi::CString;
+use std::io::{StderrLock, StdoutLock, Write};
 use std::os::raw::c_char;
+use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
+/// Options for tools that take no options beyond the common ones.
+#[derive(Args, Debug)]
+#[command(about = None, long_about = None)]
+struct NoOptions {}
+
+/// Options controlling [`upload_file_with_options`].
+#[derive(Args, Debug, Clone, Default)]
+#[command(about = None, long_about = None)]
+pub struct UploadOptions {
+    /// Create missing destination folders on the device
+    #[arg(long)]
+    pub mkdirs: bool,
+
+    /// Only upload files whose size differs from the copy on the device
+    #[arg(long)]
+    pub sync: bool,
+
+    /// With --sync, also compare the contents of files up to this many bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 0, requires = "sync")]
+    pub compare_up_to: u64,
+
+    /// Refuse to upload unless the device is this Connect IQ device ID
+    #[arg(long, value_name = "ID", requires = "devices_json")]
+    pub expect_device: Option<String>,
+
+    /// Path to the SDK's devices.json, used to identify the device
+    #[arg(long, value_name = "PATH")]
+    pub devices_json: Option<PathBuf>,
+}
+
+/// Command line options choosing the device that a tool operates on.
+#[derive(Args, Debug, Clone)]
+#[command(about = None, long_about = None)]
+pub struct DeviceArgs {
     /// Wait for device definition (seconds)
     #[arg(long, default_value_t = 60)]
-    wait_for: u64,
+    pub wait_for: u64,
+
+    /// Use a local directory as the device filesystem instead of an MTP device
+    #[arg(long, value_name = "DIR")]
+    pub device_root: Option<PathBuf>,
+
+    /// Only connect to the MTP device with this serial number
+    #[arg(long, conflicts_with = "name")]
+    pub serial: Option<String>,
+
+    /// Only connect to the MTP device with this friendly or model name
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+impl DeviceArgs {
+    pub fn selector(&self) -> DeviceSelector {
+        match (&self.serial, &self.name) {
+            (Some(serial), _) => DeviceSelector::Serial(serial.clone()),
+            (None, Some(name)) => DeviceSelector::Name(name.clone()),
+            (None, None) => DeviceSelector::First,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli<A: Args> {
+    #[command(flatten)]
+    device: DeviceArgs,
+
+    /// Run the operation on every attached MTP device in parallel
+    #[arg(long, conflicts_with_all = ["device_root", "serial", "name"])]
+    all_devices: bool,
+
+    #[command(flatten)]
+    options: A,
 
     /// Source and destination file pairs
     #[arg(num_args = 2.., value_names = ["SRC", "DST"])]
     files: Vec<String>,
 }
 
+/// Leading fields of `LIBMTP_mtpdevice_t`; the remainder is never accessed.
 #[repr(C)]
 pub struct LIBMTP_mtpdevice_t {
-    _private: [u8; 0],
+    pub object_bitsize: u8,
+    pub params: *mut c_void,
+    pub usbinfo: *mut c_void,
+    pub storage: *mut LIBMTP_devicestorage_t,
+}
+
+#[repr(C)]
+pub struct LIBMTP_devicestorage_t {
+    pub id: u32,
+    pub storage_type: u16,
+    pub filesystem_type: u16,
+    pub access_capability: u16,
+    pub max_capacity: u64,
+    pub free_space_in_bytes: u64,
+    pub free_space_in_objects: u64,
+    pub storage_description: *mut c_char,
+    pub volume_identifier: *mut c_char,
+    pub next: *mut LIBMTP_devicestorage_t,
+    pub prev: *mut LIBMTP_devicestorage_t,
+}
+
+#[repr(C)]
+pub struct LIBMTP_device_entry_t {
+    pub vendor: *mut c_char,
+    pub vendor_id: u16,
+    pub product: *mut c_char,
+    pub product_id: u16,
+    pub device_flags: u32,
+}
+
+#[repr(C)]
+pub struct LIBMTP_raw_device_t {
+    pub device_entry: LIBMTP_device_entry_t,
+    pub bus_location: u32,
+    pub devnum: u8,
 }
 
 #[repr(C)]
@@ -49,12 +171,30 @@ pub struct LIBMTP_file_t {
     pub next: *mut LIBMTP_file_t,
 }
 
+#[repr(C)]
+pub struct LIBMTP_error_t {
+    pub errornumber: i32,
+    pub error_text: *mut c_char,
+    pub next: *mut LIBMTP_error_t,
+}
+
 #[link(name = "mtp")]
 unsafe extern "C" {
     pub fn LIBMTP_Init();
     pub fn LIBMTP_Get_First_Device() -> *mut LIBMTP_mtpdevice_t;
+    pub fn LIBMTP_Detect_Raw_Devices(
+        devices: *mut *mut LIBMTP_raw_device_t,
+        numdevs: *mut i32,
+    ) -> i32;
+    pub fn LIBMTP_Open_Raw_Device_Uncached(
+        rawdevice: *mut LIBMTP_raw_device_t,
+    ) -> *mut LIBMTP_mtpdevice_t;
     pub fn LIBMTP_Release_Device(device: *mut LIBMTP_mtpdevice_t);
     pub fn LIBMTP_Get_Friendlyname(device: *mut LIBMTP_mtpdevice_t) -> *mut c_char;
+    pub fn LIBMTP_Get_Serialnumber(device: *mut LIBMTP_mtpdevice_t) -> *mut c_char;
+    pub fn LIBMTP_Get_Modelname(device: *mut LIBMTP_mtpdevice_t) -> *mut c_char;
+    pub fn LIBMTP_Get_Deviceversion(device: *mut LIBMTP_mtpdevice_t) -> *mut c_char;
+    pub fn LIBMTP_Get_Storage(device: *mut LIBMTP_mtpdevice_t, sortby: i32) -> i32;
     pub fn LIBMTP_Get_Folder_List(device: *mut LIBMTP_mtpdevice_t) -> *mut LIBMTP_folder_t;
     pub fn LIBMTP_destroy_folder_t(folder: *mut LIBMTP_folder_t);
     pub fn LIBMTP_new_file_t() -> *mut LIBMTP_file_t;
@@ -73,6 +213,15 @@ unsafe extern "C" {
         progress: Option<extern "C" fn(u64, u64, *const c_void) -> i32>,
         data: *const c_void,
     ) -> i32;
+    pub fn LIBMTP_Create_Folder(
+        device: *mut LIBMTP_mtpdevice_t,
+        name: *mut c_char,
+        parent_id: u32,
+        storage_id: u32,
+    ) -> u32;
+    pub fn LIBMTP_Delete_Object(device: *mut LIBMTP_mtpdevice_t, object_id: u32) -> i32;
+    pub fn LIBMTP_Get_Errorstack(device: *mut LIBMTP_mtpdevice_t) -> *mut LIBMTP_error_t;
+    pub fn LIBMTP_Clear_Errorstack(device: *mut LIBMTP_mtpdevice_t);
     pub fn LIBMTP_Get_Filelisting_With_Callback(
         device: *mut LIBMTP_mtpdevice_t,
         callback: Option<extern "C" fn(*mut LIBMTP_file_t, *mut c_void) -> i32>,
@@ -82,10 +231,96 @@ unsafe extern "C" {
     pub fn free(p: *mut c_void);
 }
 
+/// A folder on a device, identified by its object ID.
+#[derive(Clone, Debug)]
+pub struct FolderEntry {
+    pub folder_id: u32,
+    pub parent_id: u32,
+    pub name: String,
+}
+
+/// A file on a device, identified by its object ID.
+#[derive(Clone, Debug)]
+pub struct FileEntry {
+    pub item_id: u32,
+    pub parent_id: u32,
+    pub filename: String,
+    pub filesize: u64,
+    pub modificationdate: i64,
+}
+
+/// The primitive operations needed to transfer files to and from a device.
+///
+/// Object IDs follow MTP conventions: the root folder has ID 0, and every
+/// other folder and file is identified by the ID the transport assigned it.
+pub trait DeviceTransport {
+    /// Returns a human-readable name for the device.
+    fn friendly_name(&self) -> String;
+
+    /// Returns the serial number reported by the device, or an empty string
+    /// if it has none.
+    fn serial_number(&self) -> String {
+        String::new()
+    }
+
+    /// Returns the model name reported by the device, or an empty string if
+    /// it has none.
+    fn model_name(&self) -> String {
+        String::new()
+    }
+
+    /// Returns the firmware version reported by the device, or an empty
+    /// string if it has none.
+    fn firmware_version(&self) -> String {
+        String::new()
+    }
+
+    /// Lists every folder on the device.
+    fn folders(&self) -> Result<Vec<FolderEntry>, DeviceError>;
+
+    /// Lists every file on the device.
+    fn files(&self) -> Result<Vec<FileEntry>, DeviceError>;
+
+    /// Copies the local file `src` into the folder `parent_id` as `filename`.
+    ///
+    /// `progress` is called with the bytes sent so far and the total; the
+    /// transfer is cancelled if it returns `false`, leaving no partial file
+    /// on the device.
+    fn send_file(
+        &self,
+        src: &str,
+        parent_id: u32,
+        filename: &str,
+        filesize: u64,
+        progress: &mut dyn FnMut(u64, u64) -> bool,
+    ) -> Result<(), DeviceError>;
+
+    /// Creates a folder named `name` inside the folder `parent_id`, returning
+    /// the new folder's ID.
+    fn create_folder(&self, name: &str, parent_id: u32) -> Result<u32, DeviceError>;
+
+    /// Removes the file or folder `object_id` from the device.
+    fn delete_object(&self, object_id: u32) -> Result<(), DeviceError>;
+
+    /// Copies the file `item_id` to the local path `dst`.
+    ///
+    /// `progress` behaves as for [`DeviceTransport::send_file`].
+    fn get_file(
+        &self,
+        item_id: u32,
+        dst: &str,
+        progress: &mut dyn FnMut(u64, u64) -> bool,
+    ) -> Result<(), DeviceError>;
+}
+
 pub struct MtpDevice {
     pub raw: *mut LIBMTP_mtpdevice_t,
 }
 
+// Each device handle is only ever used by one thread at a time, and libmtp
+// keeps no shared state between handles for distinct devices.
+unsafe impl Send for MtpDevice {}
+
 impl Drop for MtpDevice {
     fn drop(&mut self) {
         unsafe {
@@ -130,10 +365,24 @@ impl Drop for MtpFileList {
 struct StreamSilencer {
     source_stream: i32,
     clone_stream: i32,
+    // Holding the Rust-side lock on the silenced stream stops other threads
+    // from printing to it, or silencing it again, while it is redirected.
+    _stdout_lock: Option<StdoutLock<'static>>,
+    _stderr_lock: Option<StderrLock<'static>>,
 }
 
 impl StreamSilencer {
     fn new(source_stream: i32) -> Option<Self> {
+        let mut stdout_lock = None;
+        let mut stderr_lock = None;
+        if source_stream == libc::STDOUT_FILENO {
+            let mut lock = std::io::stdout().lock();
+            lock.flush().ok();
+            stdout_lock = Some(lock);
+        } else if source_stream == libc::STDERR_FILENO {
+            stderr_lock = Some(std::io::stderr().lock());
+        }
+
         unsafe {
             libc::fflush(std::ptr::null_mut());
             let clone_stream = libc::dup(source_stream);
@@ -151,6 +400,8 @@ impl StreamSilencer {
             Some(StreamSilencer {
                 source_stream,
                 clone_stream,
+                _stdout_lock: stdout_lock,
+                _stderr_lock: stderr_lock,
             })
         }
     }
@@ -166,113 +417,523 @@ impl Drop for StreamSilencer {
     }
 }
 
-pub fn get_device_friendly_name(device: &MtpDevice) -> String {
-    unsafe {
-        let name_ptr = LIBMTP_Get_Friendlyname(device.raw);
-        if name_ptr.is_null() {
-            return "Unknown Device".to_string();
-        }
-        let c_str = CStr::from_ptr(name_ptr);
-        let name = c_str.to_string_lossy().into_owned();
-        free(name_ptr as *mut c_void);
-        name
+/// Converts a string allocated by libmtp into a `String`, freeing it.
+unsafe fn take_c_string(ptr: *mut c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
     }
+    let value = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+    free(ptr as *mut c_void);
+    Some(value)
 }
 
-pub fn get_folder_reference(device: &MtpDevice, path: &str) -> Result<u32, String> {
-    let folders = unsafe {
-        let _silencer = StreamSilencer::new(libc::STDOUT_FILENO);
-        LIBMTP_Get_Folder_List(device.raw)
-    };
-    if folders.is_null() {
-        return Err("Could not retrieve folder list".to_string());
+/// Forwards libmtp progress callbacks to a Rust closure passed as `data`.
+extern "C" fn progress_trampoline(sent: u64, total: u64, data: *const c_void) -> i32 {
+    let progress = unsafe { &mut *(data as *mut &mut dyn FnMut(u64, u64) -> bool) };
+    if progress(sent, total) {
+        0
+    } else {
+        1
     }
+}
 
-    let mut parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
-    if !parts.is_empty() {
-        parts.pop();
-    }
+/// A storage area on an MTP device, such as internal memory.
+#[derive(Clone, Debug)]
+pub struct StorageInfo {
+    pub description: String,
+    pub max_capacity: u64,
+    pub free_space: u64,
+}
 
-    let mut current_folder_id = 0; // Root folder ID.
-    let mut current_level_ptr = folders;
+impl MtpDevice {
+    pub fn storage(&self) -> Vec<StorageInfo> {
+        let mut storage = Vec::new();
+        unsafe {
+            if LIBMTP_Get_Storage(self.raw, 0) != 0 {
+                return storage;
+            }
+            let mut current = (*self.raw).storage;
+            while !current.is_null() {
+                let entry = &*current;
+                let description = if entry.storage_description.is_null() {
+                    String::new()
+                } else {
+                    CStr::from_ptr(entry.storage_description)
+                        .to_string_lossy()
+                        .into_owned()
+                };
+                storage.push(StorageInfo {
+                    description,
+                    max_capacity: entry.max_capacity,
+                    free_space: entry.free_space_in_bytes,
+                });
+                current = entry.next;
+            }
+        }
+        storage
+    }
 
-    for part in parts {
-        let mut found = false;
-        let mut sibling = current_level_ptr;
-
-        while !sibling.is_null() {
-            unsafe {
-                let folder = &*sibling;
-                let name_cstr = CStr::from_ptr(folder.name);
-                if let Ok(name) = name_cstr.to_str() {
-                    if name == part {
-                        current_folder_id = folder.folder_id;
-                        current_level_ptr = folder.child;
-                        found = true;
-                        break;
-                    }
+    /// Creates a [`DeviceError::TransferFailed`] with the messages on the
+    /// device's libmtp error stack as details, clearing the stack.
+    pub fn error(&self, message: &str) -> DeviceError {
+        let mut details = Vec::new();
+        unsafe {
+            let mut current = LIBMTP_Get_Errorstack(self.raw);
+            while !current.is_null() {
+                let entry = &*current;
+                if !entry.error_text.is_null() {
+                    details.push(
+                        CStr::from_ptr(entry.error_text)
+                            .to_string_lossy()
+                            .into_owned(),
+                    );
                 }
-                sibling = folder.sibling;
+                current = entry.next;
             }
+            LIBMTP_Clear_Errorstack(self.raw);
         }
+        DeviceError::TransferFailed {
+            message: message.to_string(),
+            details,
+        }
+    }
 
-        if !found {
-            unsafe {
-                LIBMTP_destroy_folder_t(folders);
+    /// Returns whether this device matches the given selector.
+    ///
+    /// Names are compared case-insensitively against both the friendly name
+    /// and the model name, as Garmin devices often leave the former unset.
+    pub fn matches(&self, selector: &DeviceSelector) -> bool {
+        match selector {
+            DeviceSelector::First => true,
+            DeviceSelector::Serial(serial) => self.serial_number() == *serial,
+            DeviceSelector::Name(name) => {
+                self.friendly_name().eq_ignore_ascii_case(name)
+                    || self.model_name().eq_ignore_ascii_case(name)
             }
-            return Err(format!("Folder '{}' not found", part));
         }
     }
+}
 
-    unsafe {
-        LIBMTP_destroy_folder_t(folders);
+impl DeviceTransport for MtpDevice {
+    fn friendly_name(&self) -> String {
+        unsafe { take_c_string(LIBMTP_Get_Friendlyname(self.raw)) }
+            .unwrap_or_else(|| "Unknown Device".to_string())
     }
 
-    Ok(current_folder_id)
-}
+    fn serial_number(&self) -> String {
+        unsafe { take_c_string(LIBMTP_Get_Serialnumber(self.raw)) }.unwrap_or_default()
+    }
 
-pub fn find_file_in_folder(
-    device: &MtpDevice,
-    parent_id: u32,
-    filename: &str,
-) -> Result<u32, String> {
-    unsafe {
-        let files_ptr =
-            LIBMTP_Get_Filelisting_With_Callback(device.raw, None, std::ptr::null_mut());
-        let _file_list = MtpFileList { head: files_ptr };
+    fn model_name(&self) -> String {
+        unsafe { take_c_string(LIBMTP_Get_Modelname(self.raw)) }.unwrap_or_default()
+    }
+
+    fn firmware_version(&self) -> String {
+        unsafe { take_c_string(LIBMTP_Get_Deviceversion(self.raw)) }.unwrap_or_default()
+    }
+
+    fn folders(&self) -> Result<Vec<FolderEntry>, DeviceError> {
+        let folders = unsafe {
+            let _silencer = StreamSilencer::new(libc::STDOUT_FILENO);
+            LIBMTP_Get_Folder_List(self.raw)
+        };
+        if folders.is_null() {
+            return Err(self.error("Could not retrieve folder list"));
+        }
+
+        // Flatten the folder tree, taking each folder's parent from its
+        // position in the tree so that top-level folders are parented to the
+        // root folder ID.
+        let mut entries = Vec::new();
+        let mut pending = vec![(folders as *const LIBMTP_folder_t, 0)];
+
+        while let Some((mut sibling, parent_id)) = pending.pop() {
+            while !sibling.is_null() {
+                unsafe {
+                    let folder = &*sibling;
+                    if !folder.name.is_null() {
+                        if let Ok(name) = CStr::from_ptr(folder.name).to_str() {
+                            entries.push(FolderEntry {
+                                folder_id: folder.folder_id,
+                                parent_id,
+                                name: name.to_string(),
+                            });
+                        }
+                    }
+                    pending.push((folder.child, folder.folder_id));
+                    sibling = folder.sibling;
+                }
+            }
+        }
+
+        unsafe {
+            LIBMTP_destroy_folder_t(folders);
+        }
+
+        Ok(entries)
+    }
 
-        let mut current_file = files_ptr;
+    fn files(&self) -> Result<Vec<FileEntry>, DeviceError> {
+        unsafe {
+            let files_ptr =
+                LIBMTP_Get_Filelisting_With_Callback(self.raw, None, std::ptr::null_mut());
+            let _file_list = MtpFileList { head: files_ptr };
+
+            let mut entries = Vec::new();
+            let mut current_file = files_ptr;
 
-        while !current_file.is_null() {
-            let file = &*current_file;
-            if file.parent_id == parent_id {
+            while !current_file.is_null() {
+                let file = &*current_file;
                 if !file.filename.is_null() {
                     let name_cstr = CStr::from_ptr(file.filename);
                     if let Ok(name) = name_cstr.to_str() {
-                        if name == filename {
-                            return Ok(file.item_id);
-                        }
+                        entries.push(FileEntry {
+                            item_id: file.item_id,
+                            parent_id: file.parent_id,
+                            filename: name.to_string(),
+                            filesize: file.filesize,
+                            modificationdate: file.modificationdate,
+                        });
                     }
                 }
+                current_file = file.next;
             }
-            current_file = file.next;
+
+            Ok(entries)
         }
+    }
 
-        Err(format!(
-            "File '{}' not found in folder ID {}",
-            filename, parent_id
-        ))
+    fn send_file(
+        &self,
+        src: &str,
+        parent_id: u32,
+        filename: &str,
+        filesize: u64,
+        progress: &mut dyn FnMut(u64, u64) -> bool,
+    ) -> Result<(), DeviceError> {
+        let filename_cstr = CString::new(filename).unwrap();
+        let mut aborted = false;
+        let mut tracked_progress = |sent, total| {
+            let keep_going = progress(sent, total);
+            aborted |= !keep_going;
+            keep_going
+        };
+        let mut tracked_progress: &mut dyn FnMut(u64, u64) -> bool = &mut tracked_progress;
+        let src_cstr = CString::new(src).unwrap();
+
+        unsafe {
+            let file_t_ptr = LIBMTP_new_file_t();
+            if file_t_ptr.is_null() {
+                return Err(DeviceError::Other(
+                    "Error allocating LIBMTP_file_t".to_string(),
+                ));
+            }
+            let _file_guard = MtpFile { raw: file_t_ptr };
+
+            (*file_t_ptr).item_id = 0;
+            (*file_t_ptr).parent_id = parent_id;
+            (*file_t_ptr).filename = strdup(filename_cstr.as_ptr());
+            (*file_t_ptr).filesize = filesize;
+            (*file_t_ptr).filetype = 43; // LIBMTP_FILETYPE_UNKNOWN
+
+            let ret = LIBMTP_Send_File_From_File(
+                self.raw,
+                src_cstr.as_ptr(),
+                file_t_ptr,
+                Some(progress_trampoline),
+                &mut tracked_progress as *mut _ as *const c_void,
+            );
+
+            if ret != 0 {
+                let error = self.error("Error uploading file");
+                // libmtp creates the object before sending its data, and
+                // leaves it behind when the transfer is cancelled.
+                let item_id = (*file_t_ptr).item_id;
+                if aborted && item_id != 0 {
+                    LIBMTP_Delete_Object(self.raw, item_id);
+                    LIBMTP_Clear_Errorstack(self.raw);
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    fn create_folder(&self, name: &str, parent_id: u32) -> Result<u32, DeviceError> {
+        let name_cstr = CString::new(name).unwrap();
+        // A storage ID of 0 places the folder on the primary storage.
+        let folder_id = unsafe {
+            LIBMTP_Create_Folder(self.raw, name_cstr.as_ptr() as *mut c_char, parent_id, 0)
+        };
+        if folder_id == 0 {
+            return Err(self.error(&format!("Error creating folder '{}'", name)));
+        }
+        Ok(folder_id)
+    }
+
+    fn delete_object(&self, object_id: u32) -> Result<(), DeviceError> {
+        let ret = unsafe { LIBMTP_Delete_Object(self.raw, object_id) };
+        if ret != 0 {
+            return Err(self.error("Error deleting file"));
+        }
+        Ok(())
+    }
+
+    fn get_file(
+        &self,
+        item_id: u32,
+        dst: &str,
+        mut progress: &mut dyn FnMut(u64, u64) -> bool,
+    ) -> Result<(), DeviceError> {
+        let dst_cstr = CString::new(dst).unwrap();
+
+        unsafe {
+            let ret = LIBMTP_Get_File_To_File(
+                self.raw,
+                item_id,
+                dst_cstr.as_ptr(),
+                Some(progress_trampoline),
+                &mut progress as *mut _ as *const c_void,
+            );
+
+            if ret != 0 {
+                return Err(self.error("Error downloading file"));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn get_device_friendly_name<T: DeviceTransport + ?Sized>(device: &T) -> String {
+    device.friendly_name()
+}
+
+/// Returns the ID of the folder containing the file at `path`.
+pub fn get_folder_reference<T: DeviceTransport + ?Sized>(
+    device: &T,
+    path: &str,
+) -> Result<u32, DeviceError> {
+    let mut parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if !parts.is_empty() {
+        parts.pop();
+    }
+
+    find_folder(&device.folders()?, &parts)
+}
+
+/// Returns the ID of the folder at `path`.
+pub fn get_folder_id<T: DeviceTransport + ?Sized>(
+    device: &T,
+    path: &str,
+) -> Result<u32, DeviceError> {
+    let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    find_folder(&device.folders()?, &parts)
+}
+
+/// Returns the ID of the folder containing the file at `path`, creating any
+/// folders along the way that do not exist yet.
+pub fn get_or_create_folder_reference<T: DeviceTransport + ?Sized>(
+    device: &T,
+    path: &str,
+) -> Result<u32, DeviceError> {
+    let folders = device.folders()?;
+
+    let mut parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if !parts.is_empty() {
+        parts.pop();
+    }
+
+    let mut current_folder_id = 0; // Root folder ID.
+    let mut current_path = String::new();
+
+    for part in parts {
+        current_path = format!("{}/{}", current_path, part);
+        let existing = folders
+            .iter()
+            .find(|folder| folder.parent_id == current_folder_id && same_name(&folder.name, part));
+        current_folder_id = match existing {
+            Some(folder) => folder.folder_id,
+            None => {
+                let folder_id = device.create_folder(part, current_folder_id)?;
+                println!("Created folder \"{}\"", current_path);
+                folder_id
+            }
+        };
+    }
+
+    Ok(current_folder_id)
+}
+
+/// Returns whether two file or folder names on a device are the same.
+///
+/// Device filesystems are FAT, so names are compared case-insensitively, e.g.
+/// `/GARMIN/Apps` is the same folder as `/GARMIN/APPS`.
+fn same_name(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+fn find_folder(folders: &[FolderEntry], parts: &[&str]) -> Result<u32, DeviceError> {
+    let mut current_folder_id = 0; // Root folder ID.
+
+    for part in parts {
+        current_folder_id = folders
+            .iter()
+            .find(|folder| folder.parent_id == current_folder_id && same_name(&folder.name, part))
+            .map(|folder| folder.folder_id)
+            .ok_or_else(|| DeviceError::FolderNotFound {
+                name: part.to_string(),
+            })?;
+    }
+
+    Ok(current_folder_id)
+}
+
+/// A file or folder found by [`list_folder`].
+#[derive(Clone, Debug)]
+pub struct ListingEntry {
+    /// Absolute path on the device, e.g. `/GARMIN/APPS/LOGS`.
+    pub path: String,
+    pub name: String,
+    /// Nesting level below the listed folder, starting at 0.
+    pub depth: usize,
+    pub is_folder: bool,
+    /// File size in bytes; `None` for folders.
+    pub size: Option<u64>,
+    /// Modification time as seconds since the Unix epoch; `None` for folders.
+    pub modificationdate: Option<i64>,
+}
+
+/// Lists the contents of the folder at `path`, sorted by name.
+///
+/// When `recursive` is set, each subfolder's contents follow its own entry.
+pub fn list_folder<T: DeviceTransport + ?Sized>(
+    device: &T,
+    path: &str,
+    recursive: bool,
+) -> Result<Vec<ListingEntry>, DeviceError> {
+    let folders = device.folders()?;
+    let files = device.files()?;
+    let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let folder_id = find_folder(&folders, &parts)?;
+
+    let mut entries = Vec::new();
+    collect_listing(
+        &folders,
+        &files,
+        folder_id,
+        &format!("/{}", parts.join("/")),
+        0,
+        recursive,
+        &mut entries,
+    );
+    Ok(entries)
+}
+
+fn collect_listing(
+    folders: &[FolderEntry],
+    files: &[FileEntry],
+    folder_id: u32,
+    folder_path: &str,
+    depth: usize,
+    recursive: bool,
+    entries: &mut Vec<ListingEntry>,
+) {
+    let join = |name: &str| format!("{}/{}", folder_path.trim_end_matches('/'), name);
+
+    let mut children: Vec<(ListingEntry, u32)> = folders
+        .iter()
+        .filter(|folder| folder.parent_id == folder_id)
+        .map(|folder| {
+            let entry = ListingEntry {
+                path: join(&folder.name),
+                name: folder.name.clone(),
+                depth,
+                is_folder: true,
+                size: None,
+                modificationdate: None,
+            };
+            (entry, folder.folder_id)
+        })
+        .chain(
+            files
+                .iter()
+                .filter(|file| file.parent_id == folder_id)
+                .map(|file| {
+                    let entry = ListingEntry {
+                        path: join(&file.filename),
+                        name: file.filename.clone(),
+                        depth,
+                        is_folder: false,
+                        size: Some(file.filesize),
+                        modificationdate: Some(file.modificationdate),
+                    };
+                    (entry, file.item_id)
+                }),
+        )
+        .collect();
+    children.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+
+    for (entry, id) in children {
+        let descend = recursive && entry.is_folder;
+        let path = entry.path.clone();
+        entries.push(entry);
+        if descend {
+            collect_listing(folders, files, id, &path, depth + 1, recursive, entries);
+        }
     }
 }
 
-pub fn upload_file(device: &MtpDevice, src: &str, dst: &str) -> Result<(), String> {
+pub fn find_file_in_folder<T: DeviceTransport + ?Sized>(
+    device: &T,
+    parent_id: u32,
+    filename: &str,
+) -> Result<u32, DeviceError> {
+    find_file_entry_in_folder(device, parent_id, filename).map(|file| file.item_id)
+}
+
+pub fn find_file_entry_in_folder<T: DeviceTransport + ?Sized>(
+    device: &T,
+    parent_id: u32,
+    filename: &str,
+) -> Result<FileEntry, DeviceError> {
+    device
+        .files()?
+        .into_iter()
+        .find(|file| file.parent_id == parent_id && same_name(&file.filename, filename))
+        .ok_or_else(|| DeviceError::FileNotFound {
+            name: filename.to_string(),
+        })
+}
+
+pub fn upload_file<T: DeviceTransport + ?Sized>(
+    device: &T,
+    src: &str,
+    dst: &str,
+) -> Result<(), DeviceError> {
+    upload_file_with_options(device, src, dst, &UploadOptions::default())
+}
+
+pub fn upload_file_with_options<T: DeviceTransport + ?Sized>(
+    device: &T,
+    src: &str,
+    dst: &str,
+    options: &UploadOptions,
+) -> Result<(), DeviceError> {
     let (dst, soft_fail) = if dst.starts_with('?') {
         (&dst[1..], true)
     } else {
         (dst, false)
     };
 
-    let folder_id = get_folder_reference(device, dst)?;
+    if let (Some(expected), Some(devices_json)) = (&options.expect_device, &options.devices_json) {
+        check_device_id(device, expected, devices_json)?;
+    }
+
+    let folder_id = if options.mkdirs {
+        get_or_create_folder_reference(device, dst)?
+    } else {
+        get_folder_reference(device, dst)?
+    };
 
     let filename = std::path::Path::new(dst)
         .file_name()
@@ -289,49 +950,168 @@ pub fn upload_file(device: &MtpDevice, src: &str, dst: &str) -> Result<(), Strin
         }
     }
 
-    let metadata = std::fs::metadata(src)
-        .map_err(|e| format!("Error getting metadata for source file '{}': {}", src, e))?;
+    let metadata = std::fs::metadata(src).map_err(|e| {
+        DeviceError::Other(format!(
+            "Error getting metadata for source file '{}': {}",
+            src, e
+        ))
+    })?;
 
-    let filename_cstr = CString::new(filename).unwrap();
-    let src_cstr = CString::new(src).unwrap();
+    if options.sync {
+        if let Ok(existing) = find_file_entry_in_folder(device, folder_id, filename) {
+            if is_unchanged(
+                device,
+                src,
+                &existing,
+                metadata.len(),
+                options.compare_up_to,
+            )? {
+                println!("File \"{}\" unchanged on device, skipping upload.", dst);
+                return Ok(());
+            }
+            // Sending a file does not replace an existing one of the same name.
+            device.delete_object(existing.item_id)?;
+        }
+    }
 
-    unsafe {
-        let file_t_ptr = LIBMTP_new_file_t();
-        if file_t_ptr.is_null() {
-            return Err("Error allocating LIBMTP_file_t".to_string());
-        }
-        let _file_guard = MtpFile { raw: file_t_ptr };
-
-        (*file_t_ptr).item_id = 0;
-        (*file_t_ptr).parent_id = folder_id;
-        (*file_t_ptr).filename = strdup(filename_cstr.as_ptr());
-        (*file_t_ptr).filesize = metadata.len();
-        (*file_t_ptr).filetype = 43; // LIBMTP_FILETYPE_UNKNOWN
-
-        let ret = LIBMTP_Send_File_From_File(
-            device.raw,
-            src_cstr.as_ptr(),
-            file_t_ptr,
-            None,
-            std::ptr::null(),
-        );
+    let mut transfer = TransferProgress::new(dst);
+    let result = device.send_file(
+        src,
+        folder_id,
+        filename,
+        metadata.len(),
+        &mut |sent, total| transfer.update(sent, total),
+    );
+    if result.is_err() {
+        transfer.abandon();
+        if is_cancelled() {
+            return Err(DeviceError::Cancelled {
+                message: format!("Upload of \"{}\" cancelled", src),
+            });
+        }
+    }
+    result?;
 
-        if ret != 0 {
-            return Err("Error uploading file".to_string());
+    println!("Uploaded \"{}\" to \"{}\"", src, dst);
+    Ok(())
+}
+
+/// Prints the bytes appended to the device file at `path` beyond `offset`,
+/// polling every `interval` until the user cancels with Ctrl-C.
+///
+/// `offset` is advanced past the printed bytes, and reset to 0 if the file
+/// shrinks. The file is re-downloaded whenever its size changes. Returns an
+/// error if the device stops responding, e.g. because it was disconnected,
+/// but not if a download fails because the user cancelled it.
+pub fn tail_file<T: DeviceTransport + ?Sized>(
+    device: &T,
+    path: &str,
+    offset: &mut u64,
+    interval: std::time::Duration,
+) -> Result<(), DeviceError> {
+    let filename = std::path::Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(path);
+    let temp_path = std::env::temp_dir().join(format!("ciq-tail-{}", std::process::id()));
+    let temp_path_str = temp_path.to_string_lossy();
+
+    while !is_cancelled() {
+        // Looking the folder up again on every poll detects disconnection.
+        let folder_id = get_folder_reference(device, path)?;
+
+        if let Ok(entry) = find_file_entry_in_folder(device, folder_id, filename) {
+            if entry.filesize < *offset {
+                *offset = 0;
+            }
+            if entry.filesize > *offset {
+                let result =
+                    device.get_file(entry.item_id, &temp_path_str, &mut |_, _| !is_cancelled());
+                if result.is_err() && is_cancelled() {
+                    let _ = std::fs::remove_file(&temp_path);
+                    break;
+                }
+                result?;
+                let contents =
+                    std::fs::read(&temp_path).map_err(|e| DeviceError::Other(e.to_string()))?;
+                let _ = std::fs::remove_file(&temp_path);
+
+                let start = (*offset as usize).min(contents.len());
+                let mut stdout = std::io::stdout();
+                stdout.write_all(&contents[start..]).ok();
+                stdout.flush().ok();
+                *offset = contents.len() as u64;
+            }
         }
 
-        println!("Uploaded \"{}\" to \"{}\"", src, dst);
+        std::thread::sleep(interval);
     }
+
     Ok(())
 }
 
-pub fn download_file(device: &MtpDevice, src: &str, dst: &str) -> Result<(), String> {
+/// Returns whether the device file `existing` matches the local file `src`.
+///
+/// Files are compared by size, and files of at most `compare_up_to` bytes are
+/// additionally downloaded and compared byte for byte.
+fn is_unchanged<T: DeviceTransport + ?Sized>(
+    device: &T,
+    src: &str,
+    existing: &FileEntry,
+    size: u64,
+    compare_up_to: u64,
+) -> Result<bool, DeviceError> {
+    if existing.filesize != size {
+        return Ok(false);
+    }
+    if size > compare_up_to {
+        return Ok(true);
+    }
+
+    let temp_path = std::env::temp_dir().join(format!(
+        "ciq-sync-{}-{}",
+        std::process::id(),
+        existing.item_id
+    ));
+    let temp_path_str = temp_path.to_string_lossy();
+    let result = device
+        .get_file(existing.item_id, &temp_path_str, &mut |_, _| {
+            !is_cancelled()
+        })
+        .and_then(|()| {
+            let remote =
+                std::fs::read(&temp_path).map_err(|e| DeviceError::Other(e.to_string()))?;
+            let local = std::fs::read(src).map_err(|e| {
+                DeviceError::Other(format!("Error reading source file '{}': {}", src, e))
+            })?;
+            Ok(remote == local)
+        });
+    let _ = std::fs::remove_file(&temp_path);
+    result
+}
+
+/// Downloads the device file `src` to the local path `dst`.
+///
+/// If `src` contains wildcards (see [`find_matching_files`]), every matching
+/// file is downloaded into the local directory `dst`, which is created if
+/// needed. Matched files keep their path relative to the last folder before
+/// the first wildcard, e.g. `/GARMIN/**/*.TXT` mirrors
+/// `/GARMIN/Apps/LOGS/APP.TXT` to `<dst>/Apps/LOGS/APP.TXT`.
+pub fn download_file<T: DeviceTransport + ?Sized>(
+    device: &T,
+    src: &str,
+    dst: &str,
+) -> Result<(), DeviceError> {
     let (src, soft_fail) = if src.starts_with('?') {
         (&src[1..], true)
     } else {
         (src, false)
     };
 
+    if is_glob(src) {
+        return download_matching_files(device, src, dst, soft_fail);
+    }
+
     let folder_id = get_folder_reference(device, src)?;
 
     let src_path = std::path::Path::new(src);
@@ -349,88 +1129,536 @@ pub fn download_file(device: &MtpDevice, src: &str, dst: &str) -> Result<(), Str
         }
     };
 
-    let dst_cstr = CString::new(dst).unwrap();
+    fetch_file(device, item_id, src, dst)
+}
 
-    unsafe {
-        let ret = LIBMTP_Get_File_To_File(
-            device.raw,
-            item_id,
-            dst_cstr.as_ptr(),
-            None,
-            std::ptr::null(),
-        );
+fn download_matching_files<T: DeviceTransport + ?Sized>(
+    device: &T,
+    pattern: &str,
+    dst: &str,
+    soft_fail: bool,
+) -> Result<(), DeviceError> {
+    let matches = match find_matching_files(device, pattern) {
+        Ok(matches) if !matches.is_empty() => matches,
+        Ok(_) | Err(DeviceError::FolderNotFound { .. }) if soft_fail => {
+            println!(
+                "No files matching \"{}\" found on device, skipping download.",
+                pattern
+            );
+            return Ok(());
+        }
+        Ok(_) => {
+            return Err(DeviceError::FileNotFound {
+                name: pattern.to_string(),
+            })
+        }
+        Err(e) => return Err(e),
+    };
 
-        if ret != 0 {
-            return Err("Error downloading file".to_string());
+    for matched in matches {
+        if is_cancelled() {
+            break;
+        }
+        let local_path = std::path::Path::new(dst).join(&matched.relative_path);
+        if let Some(parent) = local_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                DeviceError::Other(format!(
+                    "Error creating local folder '{}': {}",
+                    parent.display(),
+                    e
+                ))
+            })?;
+        }
+        fetch_file(
+            device,
+            matched.entry.item_id,
+            &matched.path,
+            &local_path.to_string_lossy(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Copies the device file `item_id`, found at `src`, to the local path `dst`
+/// while reporting progress.
+fn fetch_file<T: DeviceTransport + ?Sized>(
+    device: &T,
+    item_id: u32,
+    src: &str,
+    dst: &str,
+) -> Result<(), DeviceError> {
+    let mut transfer = TransferProgress::new(src);
+    let result = device.get_file(item_id, dst, &mut |sent, total| {
+        transfer.update(sent, total)
+    });
+    if result.is_err() {
+        transfer.abandon();
+        if is_cancelled() {
+            // Do not leave a partially downloaded file behind.
+            let _ = std::fs::remove_file(dst);
+            return Err(DeviceError::Cancelled {
+                message: format!("Download of \"{}\" cancelled", src),
+            });
         }
     }
+    result?;
 
     println!("Downloaded \"{}\" to \"{}\"", src, dst);
     Ok(())
 }
 
-pub fn init_and_get_first_device() -> Option<MtpDevice> {
+/// A device file found by [`find_matching_files`].
+#[derive(Clone, Debug)]
+pub struct MatchedFile {
+    pub entry: FileEntry,
+    /// Absolute path on the device.
+    pub path: String,
+    /// Path relative to the last folder before the first wildcard.
+    pub relative_path: String,
+}
+
+/// Returns whether a device path contains wildcards.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?'])
+}
+
+/// Lists the device files matching `pattern`, sorted by path.
+///
+/// Within a path segment, `*` matches any run of characters and `?` matches
+/// any single character. A `**` segment matches any number of nested folders,
+/// including none, so a trailing `**` matches every file below a folder.
+pub fn find_matching_files<T: DeviceTransport + ?Sized>(
+    device: &T,
+    pattern: &str,
+) -> Result<Vec<MatchedFile>, DeviceError> {
+    let mut parts: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    if parts.last() == Some(&"**") {
+        // A trailing `**` matches every file below the folder.
+        parts.push("*");
+    }
+    let literal_len = parts
+        .iter()
+        .position(|part| is_glob(part))
+        .unwrap_or(parts.len())
+        .min(parts.len().saturating_sub(1));
+
+    let folders = device.folders()?;
+    let files = device.files()?;
+    let base_id = find_folder(&folders, &parts[..literal_len])?;
+    let base_path = format!("/{}", parts[..literal_len].join("/"));
+
+    let mut matches = Vec::new();
+    collect_matches(
+        &folders,
+        &files,
+        base_id,
+        &base_path,
+        "",
+        &parts[literal_len..],
+        &mut matches,
+    );
+    matches.sort_by(|a, b| a.path.cmp(&b.path));
+    matches.dedup_by(|a, b| a.path == b.path);
+    Ok(matches)
+}
+
+fn collect_matches(
+    folders: &[FolderEntry],
+    files: &[FileEntry],
+    folder_id: u32,
+    folder_path: &str,
+    relative_path: &str,
+    parts: &[&str],
+    matches: &mut Vec<MatchedFile>,
+) {
+    let Some((&part, rest)) = parts.split_first() else {
+        return;
+    };
+    let join = |base: &str, name: &str| {
+        if base.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", base.trim_end_matches('/'), name)
+        }
+    };
+
+    if part == "**" {
+        // Match no folders at all, then descend and try again.
+        collect_matches(
+            folders,
+            files,
+            folder_id,
+            folder_path,
+            relative_path,
+            rest,
+            matches,
+        );
+    }
+
+    if rest.is_empty() && part != "**" {
+        for file in files.iter().filter(|file| file.parent_id == folder_id) {
+            if glob_match(part, &file.filename) {
+                matches.push(MatchedFile {
+                    entry: file.clone(),
+                    path: join(folder_path, &file.filename),
+                    relative_path: join(relative_path, &file.filename),
+                });
+            }
+        }
+        return;
+    }
+
+    for folder in folders
+        .iter()
+        .filter(|folder| folder.parent_id == folder_id)
+    {
+        let remaining = if part == "**" {
+            parts
+        } else if glob_match(part, &folder.name) {
+            rest
+        } else {
+            continue;
+        };
+        collect_matches(
+            folders,
+            files,
+            folder.folder_id,
+            &join(folder_path, &folder.name),
+            &join(relative_path, &folder.name),
+            remaining,
+            matches,
+        );
+    }
+}
+
+/// Opens the first attached MTP device.
+///
+/// Fails with [`DeviceError::DeviceBusy`] if a device is detected but cannot
+/// be opened, for example because another process has claimed it.
+pub fn init_and_get_first_device() -> Result<MtpDevice, DeviceError> {
     unsafe {
         let _silencer = StreamSilencer::new(libc::STDERR_FILENO);
         LIBMTP_Init();
         let raw_device = LIBMTP_Get_First_Device();
-        if raw_device.is_null() {
-            None
-        } else {
-            Some(MtpDevice { raw: raw_device })
+        if !raw_device.is_null() {
+            return Ok(MtpDevice { raw: raw_device });
+        }
+        let mut raw_devices: *mut LIBMTP_raw_device_t = std::ptr::null_mut();
+        let mut count = 0;
+        if LIBMTP_Detect_Raw_Devices(&mut raw_devices, &mut count) != 0 || raw_devices.is_null() {
+            return Err(DeviceError::NoDevice);
         }
+        free(raw_devices as *mut c_void);
     }
+    Err(DeviceError::DeviceBusy)
 }
 
+/// Opens every attached MTP device.
+///
+/// Devices that are detected but cannot be opened, for example because
+/// another process has claimed them, are skipped. Fails with
+/// [`DeviceError::DeviceBusy`] if none of the detected devices can be opened.
+pub fn init_and_get_devices() -> Result<Vec<MtpDevice>, DeviceError> {
+    let mut devices = Vec::new();
+    unsafe {
+        let _silencer = StreamSilencer::new(libc::STDERR_FILENO);
+        LIBMTP_Init();
+
+        let mut raw_devices: *mut LIBMTP_raw_device_t = std::ptr::null_mut();
+        let mut count = 0;
+        if LIBMTP_Detect_Raw_Devices(&mut raw_devices, &mut count) != 0 || raw_devices.is_null() {
+            return Err(DeviceError::NoDevice);
+        }
+
+        for i in 0..count as usize {
+            let raw_device = LIBMTP_Open_Raw_Device_Uncached(raw_devices.add(i));
+            if !raw_device.is_null() {
+                devices.push(MtpDevice { raw: raw_device });
+            }
+        }
+
+        free(raw_devices as *mut c_void);
+    }
+    if devices.is_empty() {
+        return Err(DeviceError::DeviceBusy);
+    }
+    Ok(devices)
+}
+
+/// Identifies which attached MTP device an operation should target.
+#[derive(Clone, Debug)]
+pub enum DeviceSelector {
+    First,
+    Serial(String),
+    Name(String),
+}
+
+impl std::fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceSelector::First => write!(f, "any device"),
+            DeviceSelector::Serial(serial) => write!(f, "serial \"{}\"", serial),
+            DeviceSelector::Name(name) => write!(f, "name \"{}\"", name),
+        }
+    }
+}
+
+/// Opens the attached MTP device matching `selector`, releasing all others.
+pub fn init_and_get_device(selector: &DeviceSelector) -> Result<MtpDevice, DeviceError> {
+    if let DeviceSelector::First = selector {
+        return init_and_get_first_device();
+    }
+    init_and_get_devices()?
+        .into_iter()
+        .find(|device| device.matches(selector))
+        .ok_or(DeviceError::NoDevice)
+}
+
+/// Runs `operation` on every `<src> <dst>` pair given on the command line.
+///
+/// The operation runs against the first connected MTP device, or the one
+/// chosen by `--serial` or `--name`, or against a [`DirectoryDevice`] when
+/// `--device-root` is given. With `--all-devices`, it runs against every
+/// connected MTP device in parallel.
 pub fn run_mtp_operation<F>(operation: F)
 where
-    F: Fn(&MtpDevice, &str, &str) -> Result<(), String>,
+    F: Fn(&(dyn DeviceTransport + 'static), &str, &str) -> Result<(), DeviceError> + Sync,
 {
-    let cli = Cli::parse();
+    run_mtp_operation_with_options(|_: &NoOptions, device, src, dst| operation(device, src, dst));
+}
+
+/// Like [`run_mtp_operation`], but also parses the tool-specific options `A`
+/// from the command line and passes them to `operation`.
+pub fn run_mtp_operation_with_options<A, F>(operation: F)
+where
+    A: Args + Sync,
+    F: Fn(&A, &(dyn DeviceTransport + 'static), &str, &str) -> Result<(), DeviceError> + Sync,
+{
+    let cli = Cli::<A>::parse();
+    let operation = |device: &(dyn DeviceTransport + 'static), src: &str, dst: &str| {
+        operation(&cli.options, device, src, dst)
+    };
 
     if cli.files.len() % 2 != 0 {
         eprintln!("Error: Files must be provided in pairs of <src> <dst>");
         std::process::exit(1);
     }
 
+    install_cancellation_handler();
+
+    let exit_code = if cli.all_devices {
+        run_broadcast_operation(cli.device.wait_for, &cli.files, &operation)
+    } else {
+        with_device(&cli.device, |device| {
+            run_file_pair_operation(device, &cli.files, &operation)
+        })
+        .unwrap_or_else(|e| {
+            if !is_cancelled() {
+                eprintln!("{}", e);
+            }
+            e.exit_code()
+        })
+    };
+
+    if is_cancelled() {
+        eprintln!("Cancelled");
+        std::process::exit(130);
+    }
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Deletes the file at `path` from the device.
+///
+/// If the path is prefixed with `?`, the delete will "soft-fail": if the file
+/// or its folder does not exist, nothing is deleted and no error is returned.
+pub fn delete_file<T: DeviceTransport + ?Sized>(device: &T, path: &str) -> Result<(), DeviceError> {
+    let (path, soft_fail) = match path.strip_prefix('?') {
+        Some(stripped) => (stripped, true),
+        None => (path, false),
+    };
+
+    let filename = std::path::Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(path);
+
+    let item_id = match get_folder_reference(device, path)
+        .and_then(|folder_id| find_file_in_folder(device, folder_id, filename))
+    {
+        Ok(id) => id,
+        Err(e) => {
+            if soft_fail {
+                println!("File \"{}\" not found on device, skipping delete.", path);
+                return Ok(());
+            } else {
+                return Err(e);
+            }
+        }
+    };
+
+    device.delete_object(item_id)?;
+
+    println!("Deleted \"{}\"", path);
+    Ok(())
+}
+
+/// Connects to the device chosen by `args`, waiting up to `--wait-for`
+/// seconds for it to appear, and runs `operation` on it.
+///
+/// Fails with the error of the last connection attempt if no device could be
+/// connected to.
+pub fn with_device<R, F>(args: &DeviceArgs, operation: F) -> Result<R, DeviceError>
+where
+    F: FnOnce(&(dyn DeviceTransport + 'static)) -> R,
+{
+    match &args.device_root {
+        Some(root) => wait_for_device(args.wait_for, || {
+            DirectoryDevice::open(root).ok_or(DeviceError::NoDevice)
+        })
+        .map(|d| operation(&d)),
+        None => {
+            let selector = args.selector();
+            wait_for_device(args.wait_for, || init_and_get_device(&selector)).map(|d| operation(&d))
+        }
+    }
+}
+
+/// Calls `connect` once a second until it yields a device, for at most
+/// `wait_for` seconds.
+///
+/// Only [`DeviceError::NoDevice`] and [`DeviceError::DeviceBusy`] are retried.
+fn wait_for_device<T, C>(wait_for: u64, connect: C) -> Result<T, DeviceError>
+where
+    C: Fn() -> Result<T, DeviceError>,
+{
     let start_time = std::time::Instant::now();
-    let wait_duration = std::time::Duration::from_secs(cli.wait_for);
+    let wait_duration = std::time::Duration::from_secs(wait_for);
     let mut first_attempt = true;
 
-    let device = loop {
-        match init_and_get_first_device() {
-            Some(d) => break d,
-            None => {
+    loop {
+        match connect() {
+            Ok(d) => return Ok(d),
+            Err(e @ (DeviceError::NoDevice | DeviceError::DeviceBusy)) => {
+                if is_cancelled() {
+                    return Err(DeviceError::Cancelled {
+                        message: "Cancelled while waiting for device".to_string(),
+                    });
+                }
+
                 if start_time.elapsed() >= wait_duration {
-                    eprintln!("No MTP device found, or device already claimed.");
-                    std::process::exit(1);
+                    return Err(e);
                 }
 
                 if first_attempt {
-                    eprintln!("No MTP device found, retrying...");
+                    eprintln!("{} Retrying...", e);
                     first_attempt = false;
                 }
 
                 std::thread::sleep(std::time::Duration::from_secs(1));
             }
+            Err(e) => return Err(e),
         }
-    };
+    }
+}
 
-    println!("Connected to {}", get_device_friendly_name(&device));
+/// Runs `operation` on every file pair, returning the exit code of the first
+/// failure, or 0 if all succeeded.
+fn run_file_pair_operation<F>(
+    device: &(dyn DeviceTransport + 'static),
+    files: &[String],
+    operation: &F,
+) -> i32
+where
+    F: Fn(&(dyn DeviceTransport + 'static), &str, &str) -> Result<(), DeviceError>,
+{
+    println!("Connected to {}", get_device_friendly_name(device));
 
     let mut exit_code = 0;
 
-    for chunk in cli.files.chunks(2) {
+    for chunk in files.chunks(2) {
         let src = &chunk[0];
         let dst = &chunk[1];
 
-        if let Err(e) = operation(&device, src, dst) {
+        if is_cancelled() {
+            break;
+        }
+
+        if let Err(e) = operation(device, src, dst) {
             eprintln!("{}", e);
-            exit_code = 1;
+            if exit_code == 0 {
+                exit_code = e.exit_code();
+            }
         }
     }
 
-    if exit_code != 0 {
-        std::process::exit(exit_code);
+    exit_code
+}
+
+/// Runs `operation` on every file pair against every attached MTP device,
+/// using one thread per device, then prints a per-device summary.
+///
+/// Returns the exit code of the first failure on the first failing device.
+fn run_broadcast_operation<F>(wait_for: u64, files: &[String], operation: &F) -> i32
+where
+    F: Fn(&(dyn DeviceTransport + 'static), &str, &str) -> Result<(), DeviceError> + Sync,
+{
+    let devices = match wait_for_device(wait_for, init_and_get_devices) {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("{}", e);
+            return e.exit_code();
+        }
+    };
+
+    println!("Connected to {} devices", devices.len());
+    progress::disable_progress_bars();
+
+    let results: Vec<(String, Vec<DeviceError>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = devices
+            .into_iter()
+            .map(|device| {
+                scope.spawn(move || {
+                    let label = format!(
+                        "{} ({})",
+                        device.serial_number(),
+                        get_device_friendly_name(&device)
+                    );
+                    let errors = files
+                        .chunks(2)
+                        .take_while(|_| !is_cancelled())
+                        .filter_map(|chunk| operation(&device, &chunk[0], &chunk[1]).err())
+                        .collect();
+                    (label, errors)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Device thread panicked"))
+            .collect()
+    });
+
+    let mut exit_code = 0;
+
+    println!("Summary:");
+    for (label, errors) in &results {
+        if errors.is_empty() {
+            println!("  {}: OK", label);
+        } else {
+            println!("  {}: FAILED", label);
+            for error in errors {
+                println!("    {}", error);
+            }
+            if exit_code == 0 {
+                exit_code = errors[0].exit_code();
+            }
+        }
     }
+
+    exit_code
 }
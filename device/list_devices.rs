@@ -0,0 +1,36 @@
+//! Lists the MTP devices attached to this machine.
+//!
+//! Usage:
+//!   bazel run @rules_ciq//device:list_devices
+//!
+//! For every attached device, prints the serial number, friendly name, model
+//! name, firmware version and storage areas. The serial number or name can
+//! then be passed to the upload and download tools via `--serial` or `--name`
+//! to target that device when several are attached.
+
+use lib::{format_bytes, get_device_friendly_name, init_and_get_devices, DeviceTransport};
+
+fn main() {
+    let devices = match init_and_get_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    for device in &devices {
+        println!("Serial: {}", device.serial_number());
+        println!("  Name: {}", get_device_friendly_name(device));
+        println!("  Model: {}", device.model_name());
+        println!("  Firmware: {}", device.firmware_version());
+        for storage in device.storage() {
+            println!(
+                "  Storage: {} ({} free of {})",
+                storage.description,
+                format_bytes(storage.free_space),
+                format_bytes(storage.max_capacity)
+            );
+        }
+    }
+}
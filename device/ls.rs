@@ -0,0 +1,101 @@
+//! Lists files and folders on an MTP device.
+//!
+//! Usage:
+//!   bazel run @rules_ciq//device:ls -- [--recursive] [--json] [<device_path> ...]
+//!
+//! Example:
+//!   bazel run @rules_ciq//device:ls -- --recursive /GARMIN/APPS
+//!
+//! Each listed folder is printed with its contents, showing file sizes and
+//! modification dates (UTC). With `--recursive`, subfolders are expanded as a
+//! tree. With `--json`, a JSON array of entries is printed instead, each with
+//! `path`, `type` (`file` or `folder`), `size` (bytes) and `modified`
+//! (seconds since the Unix epoch) fields; the last two are `null` for folders.
+
+use civil_date::format_date_time;
+use clap::Parser;
+use lib::{
+    format_bytes, list_folder, with_device, DeviceArgs, DeviceError, DeviceTransport,
+    ListingEntry,
+};
+use serde_json::json;
+
+const NAME_COLUMN_WIDTH: usize = 40;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(flatten)]
+    device: DeviceArgs,
+
+    /// List the contents of subfolders too
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// Print the listing as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Folders to list
+    #[arg(default_value = "/")]
+    paths: Vec<String>,
+}
+
+fn print_listing(path: &str, entries: &[ListingEntry]) {
+    println!("{}:", path);
+    for entry in entries {
+        let indent = "  ".repeat(entry.depth + 1);
+        if entry.is_folder {
+            println!("{}{}/", indent, entry.name);
+        } else {
+            println!(
+                "{}{:<width$} {:>10}  {}",
+                indent,
+                entry.name,
+                format_bytes(entry.size.unwrap_or(0)),
+                format_date_time(entry.modificationdate.unwrap_or(0), ' '),
+                width = NAME_COLUMN_WIDTH.saturating_sub(indent.len())
+            );
+        }
+    }
+}
+
+fn run(device: &dyn DeviceTransport, args: &Args) -> Result<(), DeviceError> {
+    let mut json_entries = Vec::new();
+
+    for path in &args.paths {
+        let entries = list_folder(device, path, args.recursive)?;
+
+        if args.json {
+            json_entries.extend(entries.iter().map(|entry| {
+                json!({
+                    "path": entry.path,
+                    "type": if entry.is_folder { "folder" } else { "file" },
+                    "size": entry.size,
+                    "modified": entry.modificationdate,
+                })
+            }));
+        } else {
+            print_listing(path, &entries);
+        }
+    }
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json_entries)
+                .map_err(|e| DeviceError::Other(e.to_string()))?
+        );
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Err(e) = with_device(&args.device, |device| run(device, &args)).and_then(|r| r) {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+}
@@ -0,0 +1,136 @@
+//! Progress reporting and cancellation for file transfers.
+//!
+//! On a terminal, transfers render a progress bar that is redrawn in place.
+//! Otherwise, progress is printed as one `key=value` line per update so that
+//! it can be consumed by scripts, e.g.
+//! `progress file="/GARMIN/APPS/APP.PRG" bytes=65536 total=131072 percent=50 rate=81920`.
+//!
+//! Pressing Ctrl-C while a transfer is in progress cancels it through the
+//! progress callback; pressing it again exits immediately.
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 30;
+const LINE_INTERVAL: Duration = Duration::from_millis(500);
+pub(crate) const CANCELLED_EXIT_CODE: i32 = 130;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static BARS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+extern "C" fn handle_sigint(_: libc::c_int) {
+    if CANCELLED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(CANCELLED_EXIT_CODE) };
+    }
+}
+
+/// Installs a Ctrl-C handler that requests cancellation of transfers.
+pub fn install_cancellation_handler() {
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+/// Returns whether the user has requested cancellation with Ctrl-C.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Forces line-based progress output even on a terminal.
+///
+/// Used when several transfers run at once, where progress bars redrawn in
+/// place would overwrite one another.
+pub fn disable_progress_bars() {
+    BARS_ENABLED.store(false, Ordering::SeqCst);
+}
+
+/// Formats a byte count with a binary unit suffix.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Reports the progress of a single file transfer.
+pub struct TransferProgress {
+    label: String,
+    start_time: Instant,
+    last_line_time: Option<Instant>,
+    bar: bool,
+}
+
+impl TransferProgress {
+    pub fn new(label: &str) -> Self {
+        TransferProgress {
+            label: label.to_string(),
+            start_time: Instant::now(),
+            last_line_time: None,
+            bar: BARS_ENABLED.load(Ordering::SeqCst) && std::io::stdout().is_terminal(),
+        }
+    }
+
+    /// Records that `sent` of `total` bytes have been transferred.
+    ///
+    /// Returns `false` if the transfer should be cancelled.
+    pub fn update(&mut self, sent: u64, total: u64) -> bool {
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            (sent as f64 / elapsed) as u64
+        } else {
+            0
+        };
+        // libmtp may report more bytes sent than the total.
+        let percent = (sent * 100).checked_div(total).unwrap_or(100).min(100);
+        let done = sent >= total;
+
+        if self.bar {
+            let filled = percent as usize * BAR_WIDTH / 100;
+            print!(
+                "\r{}: [{}{}] {:>3}% {}/{} {}/s",
+                self.label,
+                "#".repeat(filled),
+                " ".repeat(BAR_WIDTH - filled),
+                percent,
+                format_bytes(sent),
+                format_bytes(total),
+                format_bytes(rate)
+            );
+            if done {
+                println!();
+            }
+            std::io::stdout().flush().ok();
+        } else if done
+            || self
+                .last_line_time
+                .is_none_or(|t| t.elapsed() >= LINE_INTERVAL)
+        {
+            println!(
+                "progress file=\"{}\" bytes={} total={} percent={} rate={}",
+                self.label, sent, total, percent, rate
+            );
+            self.last_line_time = Some(Instant::now());
+        }
+
+        !is_cancelled()
+    }
+
+    /// Ends an in-place progress bar that did not reach completion.
+    pub fn abandon(&self) {
+        if self.bar {
+            println!();
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Follows a log file on an MTP device, printing new output as it appears.
+//!
+//! Usage:
+//!   bazel run @rules_ciq//device:tail <device_path>
+//!
+//! Example:
+//!   bazel run @rules_ciq//device:tail /GARMIN/Apps/LOGS/my_app.TXT
+//!
+//! The file is polled over MTP and re-downloaded whenever its size changes,
+//! and only the bytes that were not printed before are written to stdout.
+//! When the device is disconnected, e.g. to run the app, the tool waits for
+//! it to be reconnected and carries on from where it left off. Press Ctrl-C
+//! to stop.
+//!
+//! Passing `--skip-existing` prints only output written after the tool starts.
+
+use clap::Parser;
+use lib::{
+    find_file_entry_in_folder, get_device_friendly_name, get_folder_reference,
+    install_cancellation_handler, is_cancelled, tail_file, with_device, DeviceArgs,
+    DeviceTransport,
+};
+use std::path::Path;
+use std::time::Duration;
+
+/// How long to wait before connecting again after losing the device.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(flatten)]
+    device: DeviceArgs,
+
+    /// Polling interval (milliseconds)
+    #[arg(long, default_value_t = 1000)]
+    interval: u64,
+
+    /// Only print output written after the tool starts
+    #[arg(long)]
+    skip_existing: bool,
+
+    /// Path of the log file on the device
+    path: String,
+}
+
+/// Returns the size of the device file at `path`, or 0 if it does not exist.
+fn current_size(device: &dyn DeviceTransport, path: &str) -> u64 {
+    let filename = Path::new(path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(path);
+    get_folder_reference(device, path)
+        .and_then(|folder_id| find_file_entry_in_folder(device, folder_id, filename))
+        .map(|entry| entry.filesize)
+        .unwrap_or(0)
+}
+
+fn main() {
+    let args = Args::parse();
+    let interval = Duration::from_millis(args.interval);
+
+    install_cancellation_handler();
+
+    let mut offset = None;
+
+    while !is_cancelled() {
+        let connected = with_device(&args.device, |device| {
+            eprintln!("Connected to {}", get_device_friendly_name(device));
+
+            let offset = offset.get_or_insert_with(|| {
+                if args.skip_existing {
+                    current_size(device, &args.path)
+                } else {
+                    0
+                }
+            });
+
+            if let Err(e) = tail_file(device, &args.path, offset, interval) {
+                if !is_cancelled() {
+                    eprintln!("{}", e);
+                    eprintln!("Lost connection to device, waiting for it to reconnect...");
+                }
+            }
+        });
+
+        // Give up only if the device never appeared in the first place.
+        if let (Err(e), None) = (connected, offset) {
+            if !is_cancelled() {
+                eprintln!("{}", e);
+            }
+            std::process::exit(e.exit_code());
+        }
+
+        // Do not poll for the device in a tight loop, e.g. with --wait-for 0.
+        if !is_cancelled() {
+            std::thread::sleep(RECONNECT_INTERVAL);
+        }
+    }
+}
@@ -12,9 +12,31 @@
 //! If the device path is prefixed with `?` (e.g. `?/GARMIN/Apps/my_app.prg`),
 //! the upload will "soft-fail": if the file already exists on the device, it
 //! will NOT be replaced, and the tool will exit successfully.
+//!
+//! By default, every folder in the device path must already exist. Passing
+//! `--mkdirs` creates any missing folders first, e.g. `/GARMIN/Apps/LOGS` on a
+//! freshly reset device.
+//!
+//! Passing `--sync` skips files whose size matches the copy already on the
+//! device, reporting them as unchanged, and replaces the others. Adding
+//! `--compare-up-to <bytes>` also downloads device files up to that size and
+//! compares their contents, catching changes that leave the size unchanged.
+//!
+//! Passing `--device-root <dir>` performs the upload against a local directory
+//! standing in for the device filesystem, instead of a connected MTP device.
+//!
+//! Passing `--all-devices` uploads the files to every connected MTP device in
+//! parallel, then prints a per-device summary. The tool exits with a non-zero
+//! status if the upload failed on any device.
+//!
+//! On failure, the exit status identifies the kind of error, e.g. 4 when the
+//! device is claimed by another process and 6 when a file is missing. See
+//! `error.rs` for the full list.
 
-use lib::{run_mtp_operation, upload_file};
+use lib::{run_mtp_operation_with_options, upload_file_with_options, UploadOptions};
 
 fn main() {
-    run_mtp_operation(upload_file);
+    run_mtp_operation_with_options(|options: &UploadOptions, device, src, dst| {
+        upload_file_with_options(device, src, dst, options)
+    });
 }
@@ -8,12 +8,16 @@ load(
 load(
     "//device:defs.bzl",
     "ciq_sideload_app",
+    "ciq_tail_app_log",
+    "ciq_uninstall_app",
+    "ciq_view_app_crash_log",
     "ciq_view_app_log",
     "ciq_view_app_profiling",
 )
 load(
     "//simulator:defs.bzl",
     "ciq_simulation",
+    "ciq_simulator_sweep",
     "ciq_test",
 )
 
@@ -23,13 +27,17 @@ _MODE_RELEASE = "release"
 _BUILD_TEMPLATE = "{name}_{device_id}_{mode}_build"
 _PROFILING_BUILD_TEMPLATE = "{name}_{device_id}_{mode}_profiling_build"
 _BUILD_FOR_TESTING_TEMPLATE = "{name}_{device_id}_{mode}_build_for_testing"
+_VIEW_APP_CRASH_LOG_TEMPLATE = "{name}_{device_id}_{mode}_view_app_crash_log"
 _VIEW_APP_LOG_TEMPLATE = "{name}_{device_id}_{mode}_view_app_log"
+_TAIL_APP_LOG_TEMPLATE = "{name}_{device_id}_{mode}_tail_app_log"
 _VIEW_APP_PROFILING_TEMPLATE = "{name}_{device_id}_{mode}_view_app_profiling"
 _SIMULATION_TEMPLATE = "{name}_{device_id}_{mode}_simulation"
 _PROFILING_SIMULATION_TEMPLATE = "{name}_{device_id}_{mode}_profiling_simulation"
 _SIDELOAD_APP_TEMPLATE = "{name}_{device_id}_{mode}_sideload_app"
 _PROFILING_SIDELOAD_APP_TEMPLATE = "{name}_{device_id}_{mode}_profiling_sideload_app"
+_UNINSTALL_APP_TEMPLATE = "{name}_{device_id}_{mode}_uninstall_app"
 _TEST_TEMPLATE = "{name}_{device_id}_test"
+_SIMULATOR_SWEEP_TEMPLATE = "{name}_{mode}_simulator_sweep"
 
 def ciq_device_targets_macro(name, visibility = None, project = None, device_ids = devices.keys(), type_check_level = None):
     """Generates debug and release build, simulation, and device interaction targets for multiple devices.
@@ -43,6 +51,9 @@ def ciq_device_targets_macro(name, visibility = None, project = None, device_ids
     - `//path/to/package:my_app_fenix6_debug_profiling_simulation` (run)
     - `//path/to/package:my_app_fenix6_debug_sideload_app` (run)
     - `//path/to/package:my_app_fenix6_debug_simulation` (run)
+    - `//path/to/package:my_app_fenix6_debug_tail_app_log` (run)
+    - `//path/to/package:my_app_fenix6_debug_uninstall_app` (run)
+    - `//path/to/package:my_app_fenix6_debug_view_app_crash_log` (run)
     - `//path/to/package:my_app_fenix6_debug_view_app_log` (run)
     - `//path/to/package:my_app_fenix6_debug_view_app_profiling` (run)
     - `//path/to/package:my_app_fenix6_release_build` (build)
@@ -51,16 +62,25 @@ def ciq_device_targets_macro(name, visibility = None, project = None, device_ids
     - `//path/to/package:my_app_fenix6_release_profiling_simulation` (run)
     - `//path/to/package:my_app_fenix6_release_sideload_app` (run)
     - `//path/to/package:my_app_fenix6_release_simulation` (run)
+    - `//path/to/package:my_app_fenix6_release_tail_app_log` (run)
+    - `//path/to/package:my_app_fenix6_release_uninstall_app` (run)
+    - `//path/to/package:my_app_fenix6_release_view_app_crash_log` (run)
     - `//path/to/package:my_app_fenix6_release_view_app_log` (run)
     - `//path/to/package:my_app_fenix6_release_view_app_profiling` (run)
     - `//path/to/package:my_app_fenix6_test` (test)
 
+    Targets that run the application on every device in `device_ids` in one
+    simulator session are also generated:
+    - `//path/to/package:my_app_debug_simulator_sweep` (run)
+    - `//path/to/package:my_app_release_simulator_sweep` (run)
+
     These targets can be built or run using `bazel build` or `bazel run`.
     For example:
     - `bazel build //path/to/package:my_app_fenix6_debug_build`
     - `bazel run //path/to/package:my_app_fenix6_debug_simulation`
     - `bazel run //path/to/package:my_app_fenix6_debug_sideload_app`
     - `bazel test //path/to/package:my_app_fenix6_test`
+    - `bazel run //path/to/package:my_app_debug_simulator_sweep`
 
     Profiling note: The *_profiling_simulation targets ensure profiling is
     started along with the simulation, capturing startup execution. Profiling
@@ -105,6 +125,20 @@ def ciq_device_targets_macro(name, visibility = None, project = None, device_ids
                 visibility = visibility,
             )
 
+            # Tail app log
+            ciq_tail_app_log(
+                name = _TAIL_APP_LOG_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
+                device_build = _BUILD_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
+                visibility = visibility,
+            )
+
+            # View app crash log
+            ciq_view_app_crash_log(
+                name = _VIEW_APP_CRASH_LOG_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
+                device_build = _BUILD_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
+                visibility = visibility,
+            )
+
             # Simulation
             ciq_simulation(
                 name = _SIMULATION_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
@@ -133,6 +167,13 @@ def ciq_device_targets_macro(name, visibility = None, project = None, device_ids
                 visibility = visibility,
             )
 
+            # Uninstall app
+            ciq_uninstall_app(
+                name = _UNINSTALL_APP_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
+                device_build = _BUILD_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
+                visibility = visibility,
+            )
+
             # View app profiling (using the profiling build)
             ciq_view_app_profiling(
                 name = _VIEW_APP_PROFILING_TEMPLATE.format(name = name, device_id = device_id, mode = mode),
@@ -154,3 +195,15 @@ def ciq_device_targets_macro(name, visibility = None, project = None, device_ids
             device_build = _BUILD_FOR_TESTING_TEMPLATE.format(name = name, device_id = device_id, mode = _MODE_DEBUG),
             visibility = visibility,
         )
+
+    if device_ids:
+        for mode in [_MODE_DEBUG, _MODE_RELEASE]:
+            # Simulator sweep
+            ciq_simulator_sweep(
+                name = _SIMULATOR_SWEEP_TEMPLATE.format(name = name, mode = mode),
+                device_builds = [
+                    _BUILD_TEMPLATE.format(name = name, device_id = device_id, mode = mode)
+                    for device_id in device_ids
+                ],
+                visibility = visibility,
+            )
@@ -0,0 +1,650 @@
+//! Recorded activities to play back into the simulator as sensor data, read
+//! from FIT or GPX files.
+//!
+//! Only the `record` messages of a FIT file are read, and its CRC is not
+//! checked. Of a GPX file, the track points are read with their elevation and
+//! time, and the heart rate, cadence, power and speed of Garmin's
+//! `TrackPointExtension`. Speeds and distances missing from a GPX file are
+//! worked out from the positions.
+//!
+//! Playing the samples into the simulator is not implemented yet, as the
+//! shell command for setting its sensor data is undocumented.
+
+use anyhow::{bail, Context, Result};
+use civil_date::days_from_civil;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+use xml::reader::{EventReader, XmlEvent};
+
+/// The FIT global message number of `record` messages.
+const FIT_RECORD_MESSAGE: u16 = 20;
+
+/// Seconds from the Unix epoch to the FIT epoch, 1989-12-31T00:00:00Z.
+const FIT_EPOCH_OFFSET_SECS: i64 = 631_065_600;
+
+/// Mean radius of the Earth, in metres.
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// The sensor readings at one point in an activity.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sample {
+    /// Time since the start of the activity.
+    pub offset: Duration,
+    /// Position in degrees.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Altitude in metres.
+    pub altitude: Option<f64>,
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u32>,
+    /// Cadence in revolutions or steps per minute.
+    pub cadence: Option<u32>,
+    /// Speed in metres per second.
+    pub speed: Option<f64>,
+    /// Power in watts.
+    pub power: Option<u32>,
+    /// Distance from the start in metres.
+    pub distance: Option<f64>,
+}
+
+/// Reads the samples of the FIT or GPX file at `path`, in time order.
+pub fn load_activity(path: &Path) -> Result<Vec<Sample>> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    let samples = match extension.as_deref() {
+        Some("fit") => {
+            let data = std::fs::read(path)
+                .with_context(|| format!("Failed to read activity {}", path.display()))?;
+            parse_fit(&data)
+        }
+        Some("gpx") => {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to read activity {}", path.display()))?;
+            parse_gpx(BufReader::new(file))
+        }
+        _ => bail!(
+            "Unsupported activity file {}, expected .fit or .gpx",
+            path.display()
+        ),
+    }
+    .with_context(|| format!("Failed to parse activity {}", path.display()))?;
+
+    if samples.is_empty() {
+        bail!("Activity {} has no samples", path.display());
+    }
+    Ok(samples)
+}
+
+/// The layout of the messages of one FIT local message type.
+struct FitDefinition {
+    global_message: u16,
+    big_endian: bool,
+    /// The number, size and base type of each field.
+    fields: Vec<(u8, usize, u8)>,
+    /// The total size of the developer fields, which are skipped.
+    developer_size: usize,
+}
+
+fn parse_fit(data: &[u8]) -> Result<Vec<Sample>> {
+    let header_size = *data.first().context("File is empty")? as usize;
+    if data.len() < 12 || header_size < 12 || &data[8..12] != b".FIT" {
+        bail!("Not a FIT file");
+    }
+    let data_size = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+    let records = data
+        .get(header_size..header_size + data_size)
+        .context("File is truncated")?;
+
+    let mut definitions: [Option<FitDefinition>; 16] = Default::default();
+    let mut timestamps = Vec::new();
+    let mut samples = Vec::new();
+    let mut last_timestamp = 0u32;
+    let mut position = 0;
+    while position < records.len() {
+        let header = records[position];
+        position += 1;
+
+        let (local_message, compressed_offset) = if header & 0x80 != 0 {
+            ((header >> 5) & 0x03, Some(u32::from(header & 0x1F)))
+        } else if header & 0x40 != 0 {
+            let (definition, size) =
+                parse_fit_definition(&records[position..], header & 0x20 != 0)?;
+            definitions[usize::from(header & 0x0F)] = Some(definition);
+            position += size;
+            continue;
+        } else {
+            (header & 0x0F, None)
+        };
+
+        let definition = definitions[usize::from(local_message)]
+            .as_ref()
+            .with_context(|| format!("Undefined local message type {}", local_message))?;
+        let mut sample = Sample::default();
+        let mut timestamp = compressed_offset.map(|offset| {
+            let mut timestamp = (last_timestamp & !0x1F) + offset;
+            if offset < last_timestamp & 0x1F {
+                timestamp += 0x20;
+            }
+            timestamp
+        });
+        for &(number, size, base_type) in &definition.fields {
+            let bytes = records
+                .get(position..position + size)
+                .context("Message is truncated")?;
+            position += size;
+            let Some(value) = fit_value(bytes, base_type, definition.big_endian) else {
+                continue;
+            };
+            if number == 253 {
+                timestamp = Some(value as u32);
+            }
+            if definition.global_message == FIT_RECORD_MESSAGE {
+                set_fit_record_field(&mut sample, number, value);
+            }
+        }
+        position += definition.developer_size;
+
+        if let Some(timestamp) = timestamp {
+            last_timestamp = timestamp;
+        }
+        if definition.global_message == FIT_RECORD_MESSAGE {
+            timestamps.push(timestamp.unwrap_or(last_timestamp));
+            samples.push(sample);
+        }
+    }
+
+    let start = timestamps.first().copied().unwrap_or_default();
+    for (sample, timestamp) in samples.iter_mut().zip(timestamps) {
+        sample.offset = Duration::from_secs(u64::from(timestamp.saturating_sub(start)));
+    }
+    Ok(samples)
+}
+
+/// Parses a FIT definition message, returning it and its size.
+fn parse_fit_definition(
+    bytes: &[u8],
+    has_developer_fields: bool,
+) -> Result<(FitDefinition, usize)> {
+    let truncated = || anyhow::anyhow!("Definition message is truncated");
+    let fixed = bytes.get(..5).ok_or_else(truncated)?;
+    let big_endian = fixed[1] == 1;
+    let global_message = if big_endian {
+        u16::from_be_bytes([fixed[2], fixed[3]])
+    } else {
+        u16::from_le_bytes([fixed[2], fixed[3]])
+    };
+
+    let mut position = 5;
+    let mut fields = Vec::new();
+    for _ in 0..fixed[4] {
+        let field = bytes.get(position..position + 3).ok_or_else(truncated)?;
+        fields.push((field[0], usize::from(field[1]), field[2]));
+        position += 3;
+    }
+
+    let mut developer_size = 0;
+    if has_developer_fields {
+        let count = *bytes.get(position).ok_or_else(truncated)?;
+        position += 1;
+        for _ in 0..count {
+            let field = bytes.get(position..position + 3).ok_or_else(truncated)?;
+            developer_size += usize::from(field[1]);
+            position += 3;
+        }
+    }
+
+    let definition = FitDefinition {
+        global_message,
+        big_endian,
+        fields,
+        developer_size,
+    };
+    Ok((definition, position))
+}
+
+/// Reads an integer field value, or `None` if it is invalid or not an
+/// integer of up to four bytes.
+fn fit_value(bytes: &[u8], base_type: u8, big_endian: bool) -> Option<i64> {
+    let size = bytes.len();
+    if !matches!(size, 1 | 2 | 4) {
+        return None;
+    }
+    let mut raw = 0u64;
+    for i in 0..size {
+        let byte = if big_endian {
+            bytes[i]
+        } else {
+            bytes[size - 1 - i]
+        };
+        raw = raw << 8 | u64::from(byte);
+    }
+
+    let bits = size as u32 * 8;
+    let signed = matches!(base_type & 0x1F, 0x01 | 0x03 | 0x05);
+    if signed {
+        let invalid = (1u64 << (bits - 1)) - 1;
+        if raw == invalid {
+            return None;
+        }
+        // Sign extend.
+        let shift = 64 - bits;
+        Some(((raw << shift) as i64) >> shift)
+    } else {
+        // The "z" types use zero as their invalid value.
+        let invalid = if matches!(base_type & 0x1F, 0x0A..=0x0C) {
+            0
+        } else {
+            (1u64 << bits) - 1
+        };
+        if raw == invalid {
+            return None;
+        }
+        Some(raw as i64)
+    }
+}
+
+fn set_fit_record_field(sample: &mut Sample, number: u8, value: i64) {
+    let semicircles_to_degrees = |value: i64| value as f64 * 180.0 / 2f64.powi(31);
+    match number {
+        0 => sample.latitude = Some(semicircles_to_degrees(value)),
+        1 => sample.longitude = Some(semicircles_to_degrees(value)),
+        2 | 78 => sample.altitude = Some(value as f64 / 5.0 - 500.0),
+        3 => sample.heart_rate = Some(value as u32),
+        4 => sample.cadence = Some(value as u32),
+        5 => sample.distance = Some(value as f64 / 100.0),
+        6 | 73 => sample.speed = Some(value as f64 / 1000.0),
+        7 => sample.power = Some(value as u32),
+        _ => {}
+    }
+}
+
+fn parse_gpx(reader: impl std::io::Read) -> Result<Vec<Sample>> {
+    let mut samples = Vec::new();
+    let mut times = Vec::new();
+    let mut current: Option<(Sample, Option<i64>)> = None;
+    let mut element = String::new();
+
+    for event in EventReader::new(reader) {
+        match event? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                if name.local_name == "trkpt" {
+                    let mut sample = Sample::default();
+                    for attribute in attributes {
+                        match attribute.name.local_name.as_str() {
+                            "lat" => sample.latitude = attribute.value.parse().ok(),
+                            "lon" => sample.longitude = attribute.value.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                    current = Some((sample, None));
+                }
+                element = name.local_name;
+            }
+            XmlEvent::Characters(text) => {
+                let Some((sample, time)) = &mut current else {
+                    continue;
+                };
+                let text = text.trim();
+                match element.as_str() {
+                    "ele" => sample.altitude = text.parse().ok(),
+                    "time" => *time = Some(parse_time(text)?),
+                    "hr" => sample.heart_rate = text.parse().ok(),
+                    "cad" => sample.cadence = text.parse().ok(),
+                    "power" => sample.power = text.parse().ok(),
+                    "speed" => sample.speed = text.parse().ok(),
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { name } => {
+                if name.local_name == "trkpt" {
+                    if let Some((sample, time)) = current.take() {
+                        samples.push(sample);
+                        times.push(time);
+                    }
+                }
+                element.clear();
+            }
+            _ => {}
+        }
+    }
+
+    let start = times.iter().flatten().next().copied().unwrap_or_default();
+    let mut distance = 0.0;
+    for index in 0..samples.len() {
+        // Points without a time are taken to be a second after the last.
+        let seconds = match times[index] {
+            Some(time) => (time - start).max(0) as u64,
+            None if index > 0 => samples[index - 1].offset.as_secs() + 1,
+            None => 0,
+        };
+        samples[index].offset = Duration::from_secs(seconds);
+
+        if index > 0 {
+            let (previous, sample) = (&samples[index - 1], &samples[index]);
+            let step = match (
+                previous.latitude,
+                previous.longitude,
+                sample.latitude,
+                sample.longitude,
+            ) {
+                (Some(lat1), Some(lon1), Some(lat2), Some(lon2)) => {
+                    haversine(lat1, lon1, lat2, lon2)
+                }
+                _ => 0.0,
+            };
+            let elapsed = (sample.offset - previous.offset).as_secs_f64();
+            distance += step;
+            if samples[index].speed.is_none() && elapsed > 0.0 {
+                samples[index].speed = Some(step / elapsed);
+            }
+        }
+        samples[index].distance.get_or_insert(distance);
+    }
+    Ok(samples)
+}
+
+/// Returns the distance in metres between two positions given in degrees.
+fn haversine(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Parses an RFC 3339 time such as `2024-03-01T12:34:56Z`,
+/// `2024-03-01T12:34:56.250Z` or `2024-03-01T13:34:56+01:00` to seconds since
+/// the FIT epoch. Times without an offset are taken to be UTC.
+fn parse_time(text: &str) -> Result<i64> {
+    let invalid = || anyhow::anyhow!("Invalid time '{}'", text);
+    let numbers = |part: &str, separator: char| -> Result<Vec<i64>> {
+        part.split(separator)
+            .map(|number| number.parse().map_err(|_| invalid()))
+            .collect()
+    };
+
+    let (date, time) = text.split_once(['T', 't']).ok_or_else(invalid)?;
+    let (time, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0),
+        None => match time.find(['+', '-']) {
+            Some(index) => {
+                let (time, offset) = time.split_at(index);
+                let [hours, minutes] = numbers(&offset[1..], ':')?[..] else {
+                    return Err(invalid());
+                };
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                (time, sign * (hours * 3_600 + minutes * 60))
+            }
+            None => (time, 0),
+        },
+    };
+    let time = time.split('.').next().unwrap_or(time);
+
+    let [year, month, day] = numbers(date, '-')?[..] else {
+        return Err(invalid());
+    };
+    let [hour, minute, second] = numbers(time, ':')?[..] else {
+        return Err(invalid());
+    };
+
+    let month = u32::try_from(month).map_err(|_| invalid())?;
+    let day = u32::try_from(day).map_err(|_| invalid())?;
+    let days = days_from_civil(year, month, day);
+
+    Ok(days * 86_400 + hour * 3_600 + minute * 60 + second - offset - FIT_EPOCH_OFFSET_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMESTAMP: [u8; 3] = [253, 4, 0x86];
+    const HEART_RATE: [u8; 3] = [3, 1, 0x02];
+    const SPEED: [u8; 3] = [6, 2, 0x84];
+    const LATITUDE: [u8; 3] = [0, 4, 0x85];
+    const LONGITUDE: [u8; 3] = [1, 4, 0x85];
+    const CADENCE: [u8; 3] = [4, 1, 0x0A];
+
+    /// Returns a FIT definition message for `local_message` with `fields`.
+    fn definition(local_message: u8, global_message: u16, fields: &[[u8; 3]]) -> Vec<u8> {
+        let mut message = vec![0x40 | local_message, 0, 0];
+        message.extend(global_message.to_le_bytes());
+        message.push(fields.len() as u8);
+        message.extend(fields.concat());
+        message
+    }
+
+    /// Returns a FIT file of the given definition and data messages.
+    fn fit_file(records: &[u8]) -> Vec<u8> {
+        let mut file = vec![12, 0x10, 0x54, 0x08];
+        file.extend((records.len() as u32).to_le_bytes());
+        file.extend(b".FIT");
+        file.extend(records);
+        file.extend([0, 0]);
+        file
+    }
+
+    fn heart_rates(samples: &[Sample]) -> Vec<(u64, Option<u32>)> {
+        samples
+            .iter()
+            .map(|sample| (sample.offset.as_secs(), sample.heart_rate))
+            .collect()
+    }
+
+    #[test]
+    fn parses_fit_records() {
+        let mut records = definition(
+            0,
+            FIT_RECORD_MESSAGE,
+            &[TIMESTAMP, HEART_RATE, SPEED, LATITUDE, LONGITUDE],
+        );
+        for (timestamp, heart_rate, latitude) in
+            [(1_000u32, 120, 1i32 << 29), (1_001, 121, -1 << 29)]
+        {
+            records.push(0);
+            records.extend(timestamp.to_le_bytes());
+            records.push(heart_rate);
+            records.extend(2_500u16.to_le_bytes());
+            records.extend(latitude.to_le_bytes());
+            records.extend(0i32.to_le_bytes());
+        }
+
+        let samples = parse_fit(&fit_file(&records)).unwrap();
+
+        assert_eq!(
+            samples,
+            [
+                Sample {
+                    offset: Duration::ZERO,
+                    latitude: Some(45.0),
+                    longitude: Some(0.0),
+                    heart_rate: Some(120),
+                    speed: Some(2.5),
+                    ..Sample::default()
+                },
+                Sample {
+                    offset: Duration::from_secs(1),
+                    latitude: Some(-45.0),
+                    longitude: Some(0.0),
+                    heart_rate: Some(121),
+                    speed: Some(2.5),
+                    ..Sample::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_fit_compressed_timestamps() {
+        let mut records = definition(0, FIT_RECORD_MESSAGE, &[TIMESTAMP, HEART_RATE]);
+        records.extend(definition(1, FIT_RECORD_MESSAGE, &[HEART_RATE]));
+        // Timestamp 1000, which is 0x3E8.
+        records.extend([0x00, 0xE8, 0x03, 0, 0, 100]);
+        // Compressed timestamp offset 10, giving 1002.
+        records.extend([0x80 | 1 << 5 | 10, 101]);
+        // Compressed timestamp offset 2, which rolls over to 1026.
+        records.extend([0x80 | 1 << 5 | 2, 102]);
+
+        let samples = parse_fit(&fit_file(&records)).unwrap();
+
+        assert_eq!(
+            heart_rates(&samples),
+            [(0, Some(100)), (2, Some(101)), (26, Some(102))]
+        );
+    }
+
+    #[test]
+    fn parses_fit_big_endian_definitions() {
+        let mut records = vec![0x40, 0, 1];
+        records.extend(FIT_RECORD_MESSAGE.to_be_bytes());
+        records.push(3);
+        records.extend([TIMESTAMP, HEART_RATE, SPEED].concat());
+        records.extend([0x00, 0, 0, 0x03, 0xE8, 90, 0x0B, 0xB8]);
+        records.extend([0x00, 0, 0, 0x03, 0xEA, 91, 0x0F, 0xA0]);
+
+        let samples = parse_fit(&fit_file(&records)).unwrap();
+
+        assert_eq!(heart_rates(&samples), [(0, Some(90)), (2, Some(91))]);
+        assert_eq!(samples[0].speed, Some(3.0));
+        assert_eq!(samples[1].speed, Some(4.0));
+    }
+
+    #[test]
+    fn skips_fit_developer_fields_and_other_messages() {
+        // An event message, which is not a record.
+        let mut records = definition(0, 21, &[[3, 4, 0x86]]);
+        records.extend([0x00, 1, 2, 3, 4]);
+        // A record with a two-byte developer field.
+        let mut with_developer_field = definition(1, FIT_RECORD_MESSAGE, &[TIMESTAMP, HEART_RATE]);
+        with_developer_field[0] |= 0x20;
+        with_developer_field.extend([1, 0, 2, 0]);
+        records.extend(with_developer_field);
+        records.extend([0x01, 0xE8, 0x03, 0, 0, 140, 0xAA, 0xBB]);
+        records.extend([0x01, 0xE9, 0x03, 0, 0, 141, 0xAA, 0xBB]);
+
+        let samples = parse_fit(&fit_file(&records)).unwrap();
+
+        assert_eq!(heart_rates(&samples), [(0, Some(140)), (1, Some(141))]);
+    }
+
+    #[test]
+    fn leaves_out_invalid_fit_values() {
+        let mut records = definition(
+            0,
+            FIT_RECORD_MESSAGE,
+            &[TIMESTAMP, HEART_RATE, LATITUDE, CADENCE],
+        );
+        records.extend([0x00, 0xE8, 0x03, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 0]);
+        records.extend([0x00, 0xE9, 0x03, 0, 0, 150, 0, 0, 0, 0, 85]);
+
+        let samples = parse_fit(&fit_file(&records)).unwrap();
+
+        assert_eq!(samples[0].heart_rate, None);
+        assert_eq!(samples[0].latitude, None);
+        assert_eq!(samples[0].cadence, None);
+        assert_eq!(samples[1].heart_rate, Some(150));
+        assert_eq!(samples[1].latitude, Some(0.0));
+        assert_eq!(samples[1].cadence, Some(85));
+    }
+
+    #[test]
+    fn rejects_malformed_fit_files() {
+        assert!(parse_fit(b"").is_err());
+        assert!(parse_fit(b"not a fit file").is_err());
+
+        let mut records = definition(0, FIT_RECORD_MESSAGE, &[HEART_RATE]);
+        records.push(0x00);
+        let mut truncated = fit_file(&records);
+        assert!(parse_fit(&truncated).is_err());
+        truncated.truncate(14);
+        assert!(parse_fit(&truncated).is_err());
+
+        let undefined = fit_file(&[0x00, 100]);
+        assert!(parse_fit(&undefined).is_err());
+    }
+
+    #[test]
+    fn parses_gpx_track_points() {
+        let gpx = r#"<?xml version="1.0"?>
+<gpx xmlns="http://www.topografix.com/GPX/1/1"
+     xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <trk><trkseg>
+    <trkpt lat="51.5" lon="0"><ele>10</ele><time>2024-03-01T12:00:00Z</time>
+      <extensions><gpxtpx:TrackPointExtension>
+        <gpxtpx:hr>130</gpxtpx:hr><gpxtpx:cad>80</gpxtpx:cad>
+      </gpxtpx:TrackPointExtension></extensions>
+    </trkpt>
+    <trkpt lat="51.5001" lon="0"><ele>11</ele><time>2024-03-01T13:00:02+01:00</time></trkpt>
+    <trkpt lat="51.5002" lon="0"></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+        let samples = parse_gpx(gpx.as_bytes()).unwrap();
+
+        assert_eq!(samples.len(), 3);
+        assert_eq!(
+            samples
+                .iter()
+                .map(|sample| sample.offset.as_secs())
+                .collect::<Vec<_>>(),
+            [0, 2, 3]
+        );
+        assert_eq!(samples[0].heart_rate, Some(130));
+        assert_eq!(samples[0].cadence, Some(80));
+        assert_eq!(samples[0].distance, Some(0.0));
+        assert_eq!(samples[1].altitude, Some(11.0));
+        assert_eq!(samples[1].heart_rate, None);
+        let step = samples[1].distance.unwrap();
+        assert!((step - 11.119).abs() < 0.001, "{}", step);
+        assert!((samples[1].speed.unwrap() - step / 2.0).abs() < 1e-9);
+        assert!((samples[2].distance.unwrap() - 2.0 * step).abs() < 0.001);
+        assert!((samples[2].speed.unwrap() - step).abs() < 0.001);
+    }
+
+    #[test]
+    fn rejects_gpx_with_invalid_times() {
+        let gpx = r#"<gpx><trk><trkseg>
+  <trkpt lat="51.5" lon="0"><time>yesterday</time></trkpt>
+</trkseg></trk></gpx>"#;
+
+        let error = parse_gpx(gpx.as_bytes()).unwrap_err();
+
+        assert_eq!(error.to_string(), "Invalid time 'yesterday'");
+    }
+
+    #[test]
+    fn parses_rfc_3339_times() {
+        // 2024-03-01T12:34:56Z.
+        let utc = 1_709_296_496 - FIT_EPOCH_OFFSET_SECS;
+
+        assert_eq!(parse_time("2024-03-01T12:34:56Z").unwrap(), utc);
+        assert_eq!(parse_time("2024-03-01T12:34:56.250Z").unwrap(), utc);
+        assert_eq!(parse_time("2024-03-01t12:34:56z").unwrap(), utc);
+        assert_eq!(parse_time("2024-03-01T12:34:56").unwrap(), utc);
+        assert_eq!(parse_time("2024-03-01T13:34:56+01:00").unwrap(), utc);
+        assert_eq!(parse_time("2024-03-01T07:04:56.5-05:30").unwrap(), utc);
+        assert_eq!(parse_time("2024-03-02T00:04:56+11:30").unwrap(), utc);
+        assert_eq!(parse_time("2024-03-01T12:34:56+00:00").unwrap(), utc);
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        for text in [
+            "",
+            "2024-03-01",
+            "2024-03-01 12:34:56Z",
+            "2024-03-01T12:34Z",
+            "2024-03-01T12:34:56+01",
+            "2024-03-01T12:34:56+0100",
+            "2024-03-xxT12:34:56Z",
+            "2024-03-01T12:34:56Y",
+        ] {
+            assert!(parse_time(text).is_err(), "{}", text);
+        }
+    }
+}
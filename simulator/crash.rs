@@ -0,0 +1,237 @@
+//! Detection of errors reported by apps in the simulator, such as:
+//!
+//! ```text
+//! Error: Unhandled Exception
+//! Exception: UnexpectedTypeException: Expected Number/Float/Long/Double, given null
+//! Stack:
+//!   - pc: 0x10000bd8
+//!     File: 'source/HelloWorldView.mc'
+//!     Line: 25
+//!     Function: onUpdate
+//! Encountered app crash.
+//! ```
+//!
+//! Only the lines with which the simulator starts its reports are matched,
+//! case-sensitively and in full, so that an app printing its own `Error: ...`
+//! lines is not taken to have crashed. The program counters (PCs) of the
+//! stack are resolved to functions and source lines with the app's debug XML.
+
+use debug_xml::{parse_debug_xml, DebugInfo};
+use regex::Regex;
+use std::path::Path;
+
+/// The line with which the simulator ends the report of a crash.
+const CRASH_TRAILER: &str = "Encountered app crash";
+
+/// An error reported by the app.
+#[derive(Clone, Debug, Default)]
+pub struct Crash {
+    /// The line that started the report, such as `Error: Out Of Memory Error`.
+    pub error: String,
+    /// Further description of the error, such as the exception thrown.
+    pub details: Vec<String>,
+    /// The PCs of the stack, innermost first.
+    pub pcs: Vec<u32>,
+}
+
+impl Crash {
+    /// Describes the crash, with its PCs resolved using `debug_info` if given.
+    pub fn report(&self, debug_info: Option<&DebugInfo>) -> Vec<String> {
+        let mut lines = vec![self.error.clone()];
+        lines.extend(self.details.iter().map(|detail| format!("  {}", detail)));
+        for &pc in &self.pcs {
+            let location = i32::try_from(pc).ok().and_then(|pc| debug_info?.locate(pc));
+            lines.push(match location {
+                Some(location) => format!(
+                    "  at {} ({}:{}) [pc 0x{:08x}]",
+                    location.symbol, location.file, location.line, pc
+                ),
+                None => format!("  at 0x{:08x}", pc),
+            });
+        }
+        lines
+    }
+}
+
+/// Collects the errors reported in the lines of an app's log.
+pub struct CrashDetector {
+    /// Matches the lines that start the report of an error, once trimmed.
+    error_signature: Regex,
+    /// Matches `Key: value` lines within the report of an error, optionally
+    /// as the first line of a list item.
+    report_field: Regex,
+    /// The error being reported, while its report continues.
+    current: Option<Crash>,
+}
+
+impl Default for CrashDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrashDetector {
+    pub fn new() -> Self {
+        Self {
+            error_signature: Regex::new(
+                r"^(?:(?:Error: )?(?:Unhandled Exception|Out Of Memory Error|System Error)|Encountered app crash\.?)$",
+            )
+            .unwrap(),
+            report_field: Regex::new(r"^\s*(?:-\s*)?(\w+):\s*(.*)$").unwrap(),
+            current: None,
+        }
+    }
+
+    /// Feeds `text`, which may hold several lines, returning the errors whose
+    /// reports it completes.
+    pub fn feed(&mut self, text: &str) -> Vec<Crash> {
+        let mut completed = Vec::new();
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(crash) = &mut self.current {
+                if line.trim_start().starts_with(CRASH_TRAILER) {
+                    completed.extend(self.current.take());
+                    continue;
+                }
+                if continue_report(&self.report_field, crash, line) {
+                    continue;
+                }
+                completed.extend(self.current.take());
+            }
+            if self.error_signature.is_match(line.trim()) {
+                self.current = Some(Crash {
+                    error: line.trim().to_string(),
+                    ..Crash::default()
+                });
+            }
+        }
+        completed
+    }
+
+    /// Returns the error still being reported, if any, once the log has ended.
+    pub fn finish(&mut self) -> Option<Crash> {
+        self.current.take()
+    }
+}
+
+/// Adds `line` to the report of `crash`, returning whether it is part of it.
+fn continue_report(report_field: &Regex, crash: &mut Crash, line: &str) -> bool {
+    let Some(captures) = report_field.captures(line) else {
+        // Any other indented line, such as a wrapped message, is part of the
+        // report but of no interest.
+        return line.starts_with(char::is_whitespace);
+    };
+    let value = captures[2].trim();
+    match captures[1].to_ascii_lowercase().as_str() {
+        "pc" => crash.pcs.extend(parse_pc(value)),
+        "details" | "exception" | "message" => crash.details.push(line.trim().to_string()),
+        "stack" | "callstack" | "file" | "line" | "function" | "time" => {}
+        _ => return line.starts_with(char::is_whitespace),
+    }
+    true
+}
+
+fn parse_pc(value: &str) -> Option<u32> {
+    let value = value.trim_matches(|c| c == '"' || c == '\'');
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Loads the debug XML at `path` for resolving PCs, if it exists.
+///
+/// Errors reading it are printed rather than returned, as the unresolved
+/// report is still of use.
+pub(crate) fn load_debug_info(path: &Path) -> Option<DebugInfo> {
+    if !path.exists() {
+        return None;
+    }
+    match parse_debug_xml(&path.to_string_lossy()) {
+        Ok(debug_info) => Some(debug_info),
+        Err(e) => {
+            eprintln!("Failed to read {}: {:#}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(log: &str) -> Vec<Crash> {
+        let mut detector = CrashDetector::new();
+        let mut crashes = detector.feed(log);
+        crashes.extend(detector.finish());
+        crashes
+    }
+
+    #[test]
+    fn detects_unhandled_exceptions() {
+        let crashes = detect(concat!(
+            "Drawing\n",
+            "Error: Unhandled Exception\n",
+            "Exception: UnexpectedTypeException: Expected Number, given null\n",
+            "Stack: \n",
+            "  - pc: 0x10000bd8\n",
+            "    File: 'source/HelloWorldView.mc'\n",
+            "    Line: 25\n",
+            "    Function: onUpdate\n",
+            "  - pc: 0x1000002a\n",
+            "Encountered app crash.\n",
+            "Drawing\n",
+        ));
+
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].error, "Error: Unhandled Exception");
+        assert_eq!(
+            crashes[0].details,
+            ["Exception: UnexpectedTypeException: Expected Number, given null"]
+        );
+        assert_eq!(crashes[0].pcs, [0x10000bd8, 0x1000002a]);
+    }
+
+    #[test]
+    fn detects_other_reports() {
+        let crashes = detect(concat!(
+            "Error: Out Of Memory Error\n",
+            "Details: 'Failed invoking <symbol>'\n",
+            "Drawing\n",
+            "System Error\n",
+            "Encountered app crash\n",
+        ));
+
+        let errors: Vec<&str> = crashes.iter().map(|crash| crash.error.as_str()).collect();
+        assert_eq!(errors, ["Error: Out Of Memory Error", "System Error"]);
+        assert_eq!(crashes[0].details, ["Details: 'Failed invoking <symbol>'"]);
+    }
+
+    #[test]
+    fn ignores_errors_printed_by_the_app() {
+        let crashes = detect(concat!(
+            "Error: bad input\n",
+            "Details: expected a number\n",
+            "error: unhandled exception\n",
+            "System Error in the weather service\n",
+        ));
+
+        assert!(crashes.is_empty(), "{:?}", crashes);
+    }
+
+    #[test]
+    fn reports_an_unfinished_report_when_the_log_ends() {
+        let mut detector = CrashDetector::new();
+        assert!(detector.feed("Error: Unhandled Exception\nStack: \n").is_empty());
+        assert!(detector.feed("  - pc: 1234\n").is_empty());
+
+        let crash = detector.finish().unwrap();
+        assert_eq!(crash.pcs, [1234]);
+        assert!(detector.finish().is_none());
+    }
+}
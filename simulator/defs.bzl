@@ -3,6 +3,7 @@ Rules for running applications in the Connect IQ Simulator.
 """
 
 load("@bazel_skylib//lib:paths.bzl", "paths")
+load("@bazel_skylib//lib:shell.bzl", "shell")
 load("@local_ciq//sdk:defs.bzl", "SdkInfo")
 load("//build:defs.bzl", "DeviceBuildInfo", "ManifestInfo")
 
@@ -29,6 +30,17 @@ def _ciq_simulation_impl(ctx):
     device_build_info = ctx.attr.device_build[DeviceBuildInfo]
     output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
 
+    # Shell words passed to the tool for the attributes of particular rules.
+    # Files are passed as absolute paths, since the tools resolve relative
+    # paths against the directory `bazel run` was started from.
+    simulator_args = []
+    simulator_files = []
+    if hasattr(ctx.attr, "run_time"):
+        simulator_args += ["--smoke-test-run-time", str(ctx.attr.run_time)]
+    if hasattr(ctx.attr, "scenario"):
+        simulator_args += ["--scenario", '"$PWD"/' + shell.quote(ctx.file.scenario.short_path)]
+        simulator_files.append(ctx.file.scenario)
+
     # The runfiles script enables use of rlocation, and $(rlocation ...) is
     # used to find the absolute paths to particular files that seem to be in
     # different locations depending on the consumption pattern of the module.
@@ -38,7 +50,7 @@ def _ciq_simulation_impl(ctx):
         GET_APPLICATION_ID_TOOL=$(rlocation "{get_application_id_tool}")
         MANIFEST_XML_PATH=$(rlocation "{manifest_xml_path}")
         APPLICATION_ID=$($GET_APPLICATION_ID_TOOL "$MANIFEST_XML_PATH")
-        {simulator_tool} "{simulator_path}" "{shell_path}" "$APPLICATION_ID" "{prg_path}" "{debug_xml_path}" "{settings_json_path}" {device_id}
+        {simulator_tool} "{simulator_path}" "{shell_path}" "$APPLICATION_ID" "{prg_path}" "{debug_xml_path}" "{settings_json_path}" {device_id} {simulator_args} "$@"
     """
     ctx.actions.write(
         output = output_script,
@@ -53,6 +65,7 @@ def _ciq_simulation_impl(ctx):
             debug_xml_path = device_build_info.prg_debug_xml_file.short_path,
             settings_json_path = device_build_info.settings_json_file.short_path,
             device_id = device_build_info.device_id,
+            simulator_args = " ".join(simulator_args),
         ),
         is_executable = True,
     )
@@ -68,7 +81,7 @@ def _ciq_simulation_impl(ctx):
             manifest_info.manifest_file,
             ctx.executable._get_application_id_tool,
             ctx.executable._simulator_tool,
-        ],
+        ] + simulator_files,
     ).merge(ctx.attr._runfiles[DefaultInfo].default_runfiles)
 
     return [
@@ -83,7 +96,22 @@ def _ciq_simulation_impl(ctx):
 
 ciq_simulation = rule(
     implementation = _ciq_simulation_impl,
-    doc = "Creates a script to run the application in the Connect IQ Simulator.",
+    doc = """Creates a script to run the application in the Connect IQ Simulator.
+
+Errors the application reports, such as unhandled exceptions, are summarised
+with their stacks resolved to source lines, and make the script exit non-zero
+once the simulator is closed.
+
+Pass `--filter=<channels>` after `--` on the `bazel run` command line to show
+only some of the `simulator`, `device`, `app`, `test` and `raw` log channels,
+and `--log-file=<path>` to also write the session's log as timestamped JSON
+lines, e.g. to attach to a bug report.
+
+Pass `--watch` to keep the simulator running and reload the application
+whenever it is rebuilt, e.g. by `ibazel run`. Tag the target with
+`ibazel_notify_changes` so that ibazel leaves it running rather than
+restarting it.
+""",
     executable = True,
     attrs = {
         "device_build": attr.label(
@@ -109,7 +137,13 @@ ciq_simulation = rule(
 
 ciq_test = rule(
     implementation = _ciq_simulation_impl,
-    doc = "Creates a script to run the application tests in the Connect IQ Simulator.",
+    doc = """Creates a script to run the application tests in the Connect IQ Simulator.
+
+Tests can be selected with `bazel test --test_filter=<patterns>`, a
+comma-separated list of test names in which `*` and `?` are wildcards. The
+simulator still runs every test, but those not selected are reported as
+skipped and do not affect the outcome.
+""",
     test = True,
     attrs = {
         "device_build": attr.label(
@@ -132,3 +166,197 @@ ciq_test = rule(
         ),
     },
 )
+
+ciq_smoke_test = rule(
+    implementation = _ciq_simulation_impl,
+    doc = """Creates a test that runs the application in the Connect IQ Simulator for a while.
+
+The test fails if the application reports an error, such as an unhandled
+exception or running out of memory, within `run_time` seconds of starting. The
+error is logged with its stack resolved to source lines.
+""",
+    test = True,
+    attrs = {
+        "device_build": attr.label(
+            doc = "The ciq_device_build target to run in the simulator.",
+            mandatory = True,
+            providers = [SdkInfo, ManifestInfo, DeviceBuildInfo],
+        ),
+        "run_time": attr.int(
+            doc = "Seconds to run the application for.",
+            default = 10,
+        ),
+        "_runfiles": attr.label(
+            default = Label("@bazel_tools//tools/bash/runfiles"),
+        ),
+        "_get_application_id_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//build:get_application_id"),
+        ),
+        "_simulator_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//simulator:smoke_test"),
+        ),
+    },
+)
+
+ciq_scenario_test = rule(
+    implementation = _ciq_simulation_impl,
+    doc = """Creates a test that plays a scenario of shell commands to the application in the Connect IQ Simulator.
+
+The scenario is a JSON file listing steps to take in order, and optionally the
+seconds to wait for each `expect_log` step, which default to 10:
+
+```json
+{
+  "timeout": 10,
+  "steps": [
+    {"expect_log": "Ready"},
+    {"send": "<shell command>"},
+    {"expect_log": "Menu opened"},
+    {"wait": 0.5}
+  ]
+}
+```
+
+`send` sends a command to the Connect IQ shell as it is, `wait` lets the
+application run for a number of seconds, and `expect_log` waits for the
+application to log a line containing some text, which may have been logged
+since the previous `expect_log` step. The shell's protocol is undocumented, so
+no commands simulating input are provided; the scenario gives any it needs.
+
+The test fails if a step fails or the application reports an error. Run it
+with `bazel run` to watch the scenario play in the simulator.
+""",
+    test = True,
+    attrs = {
+        "device_build": attr.label(
+            doc = "The ciq_device_build target to run in the simulator.",
+            mandatory = True,
+            providers = [SdkInfo, ManifestInfo, DeviceBuildInfo],
+        ),
+        "scenario": attr.label(
+            doc = "The scenario to play, as a JSON file.",
+            mandatory = True,
+            allow_single_file = [".json"],
+        ),
+        "_runfiles": attr.label(
+            default = Label("@bazel_tools//tools/bash/runfiles"),
+        ),
+        "_get_application_id_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//build:get_application_id"),
+        ),
+        "_simulator_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//simulator:play_scenario"),
+        ),
+    },
+)
+
+def _ciq_simulator_sweep_impl(ctx):
+    sdk_info = ctx.attr.device_builds[0][SdkInfo]
+    manifest_info = ctx.attr.device_builds[0][ManifestInfo]
+    device_build_infos = [device_build[DeviceBuildInfo] for device_build in ctx.attr.device_builds]
+    output_script = ctx.actions.declare_file(ctx.label.name + ".sh")
+
+    # The first build is passed positionally, as to the other simulator tools,
+    # and the rest by `--sweep`, from which the tool derives the paths of the
+    # debug XML and settings next to each PRG.
+    sweep_args = ["--sweep-run-time", str(ctx.attr.run_time)]
+    for device_build_info in device_build_infos[1:]:
+        sweep_args += ["--sweep", "{}={}".format(device_build_info.device_id, device_build_info.prg_file.short_path)]
+
+    script_content = """
+        #!/usr/bin/env bash
+        {runfiles_boilerplate}
+        GET_APPLICATION_ID_TOOL=$(rlocation "{get_application_id_tool}")
+        MANIFEST_XML_PATH=$(rlocation "{manifest_xml_path}")
+        APPLICATION_ID=$($GET_APPLICATION_ID_TOOL "$MANIFEST_XML_PATH")
+        {sweep_tool} "{simulator_path}" "{shell_path}" "$APPLICATION_ID" "{prg_path}" "{debug_xml_path}" "{settings_json_path}" {device_id} {sweep_args} "$@"
+    """
+    ctx.actions.write(
+        output = output_script,
+        content = script_content.format(
+            runfiles_boilerplate = _BASH_RUNFILES_BOILERPLATE,
+            get_application_id_tool = paths.normalize(paths.join(ctx.workspace_name, ctx.executable._get_application_id_tool.short_path)),
+            manifest_xml_path = paths.normalize(paths.join(ctx.workspace_name, manifest_info.manifest_file.short_path)),
+            sweep_tool = ctx.executable._sweep_tool.short_path,
+            simulator_path = sdk_info.simulator_path,
+            shell_path = sdk_info.shell_path,
+            prg_path = device_build_infos[0].prg_file.short_path,
+            debug_xml_path = device_build_infos[0].prg_debug_xml_file.short_path,
+            settings_json_path = device_build_infos[0].settings_json_file.short_path,
+            device_id = device_build_infos[0].device_id,
+            sweep_args = " ".join([shell.quote(arg) for arg in sweep_args]),
+        ),
+        is_executable = True,
+    )
+
+    app_files = []
+    for device_build_info in device_build_infos:
+        app_files += [
+            device_build_info.prg_file,
+            device_build_info.prg_debug_xml_file,
+            device_build_info.settings_json_file,
+        ]
+
+    runfiles = ctx.runfiles(
+        files = [
+            output_script,
+            manifest_info.manifest_file,
+            ctx.executable._get_application_id_tool,
+            ctx.executable._sweep_tool,
+        ] + app_files,
+    ).merge(ctx.attr._runfiles[DefaultInfo].default_runfiles)
+
+    return [
+        DefaultInfo(
+            executable = output_script,
+            runfiles = runfiles,
+        ),
+        testing.ExecutionInfo({
+            "local": "1",
+        }),
+    ]
+
+ciq_simulator_sweep = rule(
+    implementation = _ciq_simulator_sweep_impl,
+    doc = """Creates a script to run the application on several devices in one Connect IQ Simulator session.
+
+Each device is opened in turn and the application is run on it for `run_time`
+seconds, after which a table of the errors logged on each device is printed.
+The script exits non-zero if any device failed to run the application or
+logged an error.
+""",
+    executable = True,
+    attrs = {
+        "device_builds": attr.label_list(
+            doc = "The ciq_device_build targets, one per device, to run in the simulator.",
+            mandatory = True,
+            allow_empty = False,
+            providers = [SdkInfo, ManifestInfo, DeviceBuildInfo],
+        ),
+        "run_time": attr.int(
+            doc = "Seconds to run the application on each device.",
+            default = 5,
+        ),
+        "_runfiles": attr.label(
+            default = Label("@bazel_tools//tools/bash/runfiles"),
+        ),
+        "_get_application_id_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//build:get_application_id"),
+        ),
+        "_sweep_tool": attr.label(
+            executable = True,
+            cfg = "exec",
+            default = Label("//simulator:sweep"),
+        ),
+    },
+)
@@ -0,0 +1,211 @@
+//! A stand-in for the Connect IQ SDK's `shell`, for testing the simulator
+//! tools without the SDK or a display.
+//!
+//! It speaks enough of the shell protocol for `run_simulator_operation`:
+//!
+//! - `push "<source>" "<destination>"` succeeds if the source file exists.
+//! - `ciq` replies `[1][0]shellConnected`.
+//! - `[1][0]openDevice <device>` replies `[1][0]deviceStarted <device>`.
+//! - `[2][0]startApp <app>` and `[2][0]runAllTests <app>` play back the
+//!   script, then exit, ending the log stream. A `$HANG` line in the script
+//!   stops it there without exiting, and an `$AWAIT <prefix>` line pauses it
+//!   until a command starting with `<prefix>` is received.
+//!
+//! It is configured through the environment, which the tools pass on:
+//!
+//! - `FAKE_SHELL_SCRIPT`: file of lines to print after the app starts, in
+//!   which `$APP` is replaced with the app ID from the start command. Lines
+//!   starting with `@<device> ` are only printed when that device is open.
+//! - `FAKE_SHELL_LOG`: file to which every command received is appended.
+//! - `FAKE_SHELL_PUSH_DIR`: directory into which pushed files are copied,
+//!   under their destination path with the `0:/` volume stripped.
+//! - `FAKE_SHELL_HANG_ON`: command prefix which is never replied to, such as
+//!   `[1][0]openDevice`, to simulate a wedged simulator.
+//! - `FAKE_SHELL_LOCK`: file that exists while a shell runs. A shell started
+//!   while it exists prints no prompt and exits, so that the tools are shown
+//!   never to connect two shells to the simulator at once.
+//!
+//! The shell exits at the end of its input, so a hung shell exits with the
+//! tool that started it.
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+const PROMPT: &str = ":>";
+
+fn main() -> io::Result<()> {
+    let _lock = match env::var_os("FAKE_SHELL_LOCK") {
+        Some(path) => match Lock::acquire(PathBuf::from(path))? {
+            Some(lock) => Some(lock),
+            None => {
+                eprintln!("Another shell is connected to the simulator");
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    run()
+}
+
+/// Marks a shell as connected to the simulator until dropped.
+struct Lock(PathBuf);
+
+impl Lock {
+    /// Creates the lock file at `path`, or returns `None` if it exists.
+    fn acquire(path: PathBuf) -> io::Result<Option<Self>> {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => Ok(Some(Lock(path))),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Replies to the commands read from stdin until it ends.
+fn run() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut log = match env::var_os("FAKE_SHELL_LOG") {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+
+    let hang_on = env::var("FAKE_SHELL_HANG_ON").ok();
+    let mut hung = false;
+    let mut open_device = String::new();
+    // The rest of the script, and the command prefix it is paused until.
+    let mut script = VecDeque::new();
+    let mut awaiting: Option<String> = None;
+
+    write!(stdout, "{}", PROMPT)?;
+    stdout.flush()?;
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+        let command = line.trim();
+        if let Some(log) = &mut log {
+            writeln!(log, "{}", command)?;
+        }
+        if hung
+            || hang_on
+                .as_deref()
+                .is_some_and(|prefix| command.starts_with(prefix))
+        {
+            hung = true;
+            continue;
+        }
+
+        if awaiting
+            .as_deref()
+            .is_some_and(|prefix| command.starts_with(prefix))
+        {
+            awaiting = None;
+            if play_script(&mut stdout, &mut script, &mut awaiting)? {
+                return Ok(());
+            }
+        } else if let Some(rest) = command.strip_prefix("push ") {
+            match push(rest) {
+                Ok(()) => writeln!(stdout, "File pushed successfully")?,
+                Err(e) => writeln!(stdout, "Failed to push file: {}", e)?,
+            }
+            write!(stdout, "{}", PROMPT)?;
+        } else if command == "ciq" {
+            writeln!(stdout, "[1][0]shellConnected")?;
+        } else if let Some(device) = command.strip_prefix("[1][0]openDevice ") {
+            writeln!(stdout, "[1][0]deviceStarted {}", device)?;
+            open_device = device.to_string();
+        } else if let Some(app) = command
+            .strip_prefix("[2][0]startApp ")
+            .or_else(|| command.strip_prefix("[2][0]runAllTests "))
+        {
+            script = load_script(app, &open_device)?;
+            if play_script(&mut stdout, &mut script, &mut awaiting)? {
+                return Ok(());
+            }
+        } else {
+            writeln!(stdout, "Unknown command: {}", command)?;
+        }
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Handles the arguments of a `push` command.
+fn push(arguments: &str) -> io::Result<()> {
+    let paths: Vec<&str> = arguments
+        .split('"')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    let [source, destination] = paths[..] else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "expected a source and a destination",
+        ));
+    };
+
+    let contents = fs::read(source)?;
+    if let Some(push_dir) = env::var_os("FAKE_SHELL_PUSH_DIR") {
+        let relative = destination
+            .split_once(":/")
+            .map_or(destination, |(_, path)| path);
+        let target = PathBuf::from(push_dir).join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(target, contents)?;
+    }
+    Ok(())
+}
+
+/// Returns the lines of `$FAKE_SHELL_SCRIPT` for the app `app` on `device`.
+fn load_script(app: &str, device: &str) -> io::Result<VecDeque<String>> {
+    let Some(path) = env::var_os("FAKE_SHELL_SCRIPT") else {
+        return Ok(VecDeque::new());
+    };
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(
+            |line| match line.strip_prefix('@').and_then(|rest| rest.split_once(' ')) {
+                Some((only_on, rest)) if only_on == device => Some(rest),
+                Some(_) => None,
+                None => Some(line),
+            },
+        )
+        .map(|line| line.replace("$APP", app))
+        .collect())
+}
+
+/// Prints the lines of `script` up to its end, a `$HANG` line or an `$AWAIT`
+/// line, returning whether it reached its end.
+fn play_script(
+    stdout: &mut io::Stdout,
+    script: &mut VecDeque<String>,
+    awaiting: &mut Option<String>,
+) -> io::Result<bool> {
+    while let Some(line) = script.pop_front() {
+        if line == "$HANG" {
+            script.clear();
+            stdout.flush()?;
+            return Ok(false);
+        }
+        if let Some(prefix) = line.strip_prefix("$AWAIT ") {
+            *awaiting = Some(prefix.to_string());
+            stdout.flush()?;
+            return Ok(false);
+        }
+        writeln!(stdout, "{}", line)?;
+    }
+    stdout.flush()?;
+    Ok(true)
+}
@@ -0,0 +1,499 @@
+//! Per-test results of a simulator test run, and their JUnit XML report.
+//!
+//! The simulator reports each test on the test channel as it runs:
+//!
+//! ```text
+//! Executing test sampleTest...
+//! DEBUG (14:32): This is a debug message.
+//! PASS
+//! ```
+//!
+//! followed by a `RESULTS` table listing every test with its status, and a
+//! final `PASSED (passed=1, failed=0, errors=0)` or `FAILED (...)` line.
+//!
+//! The simulator can only run all of an app's tests, so selecting tests with a
+//! [`TestFilter`] happens after the fact: tests that do not match are reported
+//! as skipped, and their outcome is ignored.
+
+use anyhow::{Context, Result};
+use glob_match::glob_match;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestStatus {
+    Pass,
+    Fail,
+    Error,
+    /// The test ran, but was not selected by the [`TestFilter`].
+    Skipped,
+}
+
+impl TestStatus {
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "PASS" => Some(TestStatus::Pass),
+            "FAIL" => Some(TestStatus::Fail),
+            "ERROR" => Some(TestStatus::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TestCase {
+    pub name: String,
+    /// `None` if the test never reported a status, e.g. because the
+    /// simulator stopped while it was running.
+    pub status: Option<TestStatus>,
+    /// Everything the test logged between starting and reporting its status.
+    pub output: String,
+    pub duration: Duration,
+}
+
+impl TestCase {
+    /// Returns a one-line description of why the test did not pass.
+    fn failure_message(&self) -> String {
+        let mut lines = self.output.lines().map(str::trim).filter(|l| !l.is_empty());
+        let message = match self.status {
+            Some(TestStatus::Error) => lines
+                .clone()
+                .find(|line| line.starts_with("Error:"))
+                .or_else(|| lines.next_back()),
+            _ => lines.next_back(),
+        };
+        message.map_or_else(
+            || match self.status {
+                Some(TestStatus::Fail) => "Test failed".to_string(),
+                Some(TestStatus::Error) => "Test raised an error".to_string(),
+                _ => "Test did not complete".to_string(),
+            },
+            str::to_string,
+        )
+    }
+}
+
+/// Selects tests by name, from a comma-separated list of names or patterns
+/// where `*` matches any run of characters and `?` any single character, as
+/// with `bazel test --test_filter=sampleTest,*Layout*`.
+#[derive(Clone, Debug)]
+pub struct TestFilter {
+    patterns: Vec<String>,
+}
+
+impl TestFilter {
+    /// Returns `None` if `filter` contains no patterns.
+    pub fn parse(filter: &str) -> Option<Self> {
+        let patterns: Vec<String> = filter
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(str::to_string)
+            .collect();
+        (!patterns.is_empty()).then_some(Self { patterns })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, name))
+    }
+}
+
+impl std::fmt::Display for TestFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.patterns.join(","))
+    }
+}
+
+/// Collects [`TestCase`]s from the output of a test run.
+#[derive(Default)]
+pub struct TestReport {
+    pub cases: Vec<TestCase>,
+    /// The final `PASSED (...)` or `FAILED (...)` line.
+    pub summary: Option<String>,
+    current: Option<(usize, Instant)>,
+    in_results: bool,
+}
+
+impl TestReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes text from the test channel.
+    pub fn feed_test_output(&mut self, text: &str) {
+        for line in text.lines() {
+            let trimmed = line.trim();
+
+            if let Some(name) = trimmed
+                .strip_prefix("Executing test ")
+                .map(|rest| rest.trim_end_matches("..."))
+            {
+                self.finish_current(None);
+                self.cases.push(TestCase {
+                    name: name.to_string(),
+                    status: None,
+                    output: String::new(),
+                    duration: Duration::ZERO,
+                });
+                self.current = Some((self.cases.len() - 1, Instant::now()));
+            } else if let Some(status) = TestStatus::parse(trimmed) {
+                self.finish_current(Some(status));
+            } else if trimmed == "RESULTS" {
+                self.finish_current(None);
+                self.in_results = true;
+            } else if trimmed.starts_with("PASSED (") || trimmed.starts_with("FAILED (") {
+                self.summary = Some(trimmed.to_string());
+            } else if self.in_results {
+                self.record_result(trimmed);
+            } else if self.current.is_some() {
+                self.append_output(line);
+            }
+        }
+    }
+
+    /// Processes text logged by the app, which belongs to the running test.
+    pub fn feed_app_output(&mut self, text: &str) {
+        if self.current.is_some() {
+            for line in text.lines() {
+                self.append_output(line);
+            }
+        }
+    }
+
+    /// Returns the name of the test that has started but not yet finished.
+    pub fn running(&self) -> Option<&str> {
+        self.current
+            .map(|(index, _)| self.cases[index].name.as_str())
+    }
+
+    /// Marks every test that `filter` does not select as skipped.
+    pub fn apply_filter(&mut self, filter: &TestFilter) {
+        self.finish_current(None);
+        for case in &mut self.cases {
+            if !filter.matches(&case.name) {
+                case.status = Some(TestStatus::Skipped);
+            }
+        }
+    }
+
+    /// Returns the tests that were skipped by [`TestReport::apply_filter`].
+    pub fn skipped(&self) -> impl Iterator<Item = &TestCase> {
+        self.cases
+            .iter()
+            .filter(|case| case.status == Some(TestStatus::Skipped))
+    }
+
+    /// Returns the number of tests with each outcome that is not a pass, as
+    /// `(failures, errors)`. Tests without a status count as errors.
+    pub fn failure_counts(&self) -> (usize, usize) {
+        let failures = self
+            .cases
+            .iter()
+            .filter(|case| case.status == Some(TestStatus::Fail))
+            .count();
+        let errors = self
+            .cases
+            .iter()
+            .filter(|case| matches!(case.status, None | Some(TestStatus::Error)))
+            .count();
+        (failures, errors)
+    }
+
+    fn append_output(&mut self, line: &str) {
+        if let Some((index, _)) = self.current {
+            let output = &mut self.cases[index].output;
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    fn finish_current(&mut self, status: Option<TestStatus>) {
+        if let Some((index, started)) = self.current.take() {
+            let case = &mut self.cases[index];
+            case.status = status;
+            case.duration = started.elapsed();
+        }
+    }
+
+    /// Records a `<name> <status>` row of the results table, adding tests
+    /// that were not seen running.
+    fn record_result(&mut self, row: &str) {
+        let Some((name, status)) = row
+            .rsplit_once(char::is_whitespace)
+            .and_then(|(name, status)| Some((name.trim(), TestStatus::parse(status)?)))
+        else {
+            return;
+        };
+
+        match self.cases.iter_mut().find(|case| case.name == name) {
+            Some(case) => case.status = Some(status),
+            None => self.cases.push(TestCase {
+                name: name.to_string(),
+                status: Some(status),
+                output: String::new(),
+                duration: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Writes the results as a JUnit XML test suite named `suite_name`.
+    pub fn write_junit(&mut self, path: &Path, suite_name: &str) -> Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to write JUnit XML to {}", path.display()))?;
+        self.write_junit_to(std::io::BufWriter::new(file), suite_name)
+            .with_context(|| format!("Failed to write JUnit XML to {}", path.display()))
+    }
+
+    fn write_junit_to<W: Write>(&mut self, sink: W, suite_name: &str) -> Result<()> {
+        self.finish_current(None);
+
+        let (failures, errors) = self.failure_counts();
+        let total: Duration = self.cases.iter().map(|case| case.duration).sum();
+        let suite_name = xml_text(suite_name);
+
+        let mut writer = EmitterConfig::new()
+            .perform_indent(true)
+            .create_writer(sink);
+        writer.write(XmlEvent::start_element("testsuites"))?;
+        writer.write(
+            XmlEvent::start_element("testsuite")
+                .attr("name", &suite_name)
+                .attr("tests", &self.cases.len().to_string())
+                .attr("failures", &failures.to_string())
+                .attr("errors", &errors.to_string())
+                .attr("skipped", &self.skipped().count().to_string())
+                .attr("time", &format!("{:.3}", total.as_secs_f64())),
+        )?;
+        for case in &self.cases {
+            write_test_case(&mut writer, case, &suite_name)?;
+        }
+        writer.write(XmlEvent::end_element())?;
+        writer.write(XmlEvent::end_element())?;
+        writer.into_inner().flush()?;
+        Ok(())
+    }
+}
+
+fn write_test_case<W: Write>(
+    writer: &mut EventWriter<W>,
+    case: &TestCase,
+    suite_name: &str,
+) -> Result<()> {
+    writer.write(
+        XmlEvent::start_element("testcase")
+            .attr("name", &xml_text(&case.name))
+            .attr("classname", suite_name)
+            .attr("time", &format!("{:.3}", case.duration.as_secs_f64())),
+    )?;
+    let output = xml_text(&case.output);
+    match case.status {
+        Some(TestStatus::Pass) if output.is_empty() => {}
+        Some(TestStatus::Pass) => {
+            writer.write(XmlEvent::start_element("system-out"))?;
+            writer.write(XmlEvent::characters(&output))?;
+            writer.write(XmlEvent::end_element())?;
+        }
+        Some(TestStatus::Skipped) => {
+            writer.write(XmlEvent::start_element("skipped"))?;
+            writer.write(XmlEvent::end_element())?;
+        }
+        Some(TestStatus::Fail | TestStatus::Error) | None => {
+            let element = match case.status {
+                Some(TestStatus::Fail) => "failure",
+                _ => "error",
+            };
+            let message = xml_text(&case.failure_message());
+            writer.write(XmlEvent::start_element(element).attr("message", &message))?;
+            if !output.is_empty() {
+                writer.write(XmlEvent::characters(&output))?;
+            }
+            writer.write(XmlEvent::end_element())?;
+        }
+    }
+    writer.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// Removes the control characters that are not allowed in XML, such as those
+/// of terminal escape sequences, from text.
+fn xml_text(text: &str) -> String {
+    text.chars()
+        .filter(|&c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_OUTPUT: &str = "\
+Executing test passingTest...
+PASS
+Executing test failingTest...
+ASSERTION FAILED
+FAIL
+Executing test erroringTest...
+Error: Unhandled Exception
+Details: 'Failed invoking <symbol>'
+ERROR
+RESULTS
+Test:                                     Status:
+passingTest                               PASS
+failingTest                               FAIL
+erroringTest                              ERROR
+unseenTest                                PASS
+FAILED (passed=2, failed=1, errors=1)
+";
+
+    fn report() -> TestReport {
+        let mut report = TestReport::new();
+        report.feed_test_output(TEST_OUTPUT);
+        report
+    }
+
+    fn statuses(report: &TestReport) -> Vec<(&str, Option<TestStatus>)> {
+        report
+            .cases
+            .iter()
+            .map(|case| (case.name.as_str(), case.status))
+            .collect()
+    }
+
+    #[test]
+    fn parses_test_statuses_output_and_summary() {
+        let report = report();
+
+        assert_eq!(
+            statuses(&report),
+            [
+                ("passingTest", Some(TestStatus::Pass)),
+                ("failingTest", Some(TestStatus::Fail)),
+                ("erroringTest", Some(TestStatus::Error)),
+                ("unseenTest", Some(TestStatus::Pass)),
+            ]
+        );
+        assert_eq!(report.cases[1].output, "ASSERTION FAILED\n");
+        assert_eq!(
+            report.summary.as_deref(),
+            Some("FAILED (passed=2, failed=1, errors=1)")
+        );
+        assert_eq!(report.failure_counts(), (1, 1));
+    }
+
+    #[test]
+    fn attributes_app_output_to_the_running_test() {
+        let mut report = TestReport::new();
+        report.feed_app_output("Before any test\n");
+        report.feed_test_output("Executing test sampleTest...\n");
+        report.feed_app_output("Hello from the app\n");
+
+        assert_eq!(report.running(), Some("sampleTest"));
+
+        report.feed_test_output("PASS\n");
+        report.feed_app_output("After the test\n");
+
+        assert_eq!(report.running(), None);
+        assert_eq!(report.cases[0].output, "Hello from the app\n");
+    }
+
+    #[test]
+    fn counts_tests_without_a_status_as_errors() {
+        let mut report = TestReport::new();
+        report.feed_test_output("Executing test passingTest...\nPASS\n");
+        report.feed_test_output("Executing test crashingTest...\n");
+        report.feed_app_output("Out of memory\n");
+        report.apply_filter(&TestFilter::parse("*").unwrap());
+
+        assert_eq!(report.cases[1].status, None);
+        assert_eq!(report.failure_counts(), (0, 1));
+        assert_eq!(report.cases[1].failure_message(), "Out of memory");
+    }
+
+    #[test]
+    fn failure_message_prefers_error_line() {
+        let report = report();
+
+        assert_eq!(report.cases[1].failure_message(), "ASSERTION FAILED");
+        assert_eq!(
+            report.cases[2].failure_message(),
+            "Error: Unhandled Exception"
+        );
+        assert_eq!(report.cases[3].failure_message(), "Test did not complete");
+    }
+
+    #[test]
+    fn filter_parses_comma_separated_patterns() {
+        assert!(TestFilter::parse("").is_none());
+        assert!(TestFilter::parse(" , ").is_none());
+
+        let filter = TestFilter::parse("passingTest, *ing*Test ,unseen?est").unwrap();
+
+        assert_eq!(filter.to_string(), "passingTest,*ing*Test,unseen?est");
+        assert!(filter.matches("passingTest"));
+        assert!(filter.matches("failingTest"));
+        assert!(filter.matches("unseenTest"));
+        assert!(!filter.matches("unseenTests"));
+        assert!(!filter.matches("otherTest"));
+    }
+
+    #[test]
+    fn apply_filter_skips_unselected_tests() {
+        let mut report = report();
+
+        report.apply_filter(&TestFilter::parse("passingTest,failingTest").unwrap());
+
+        assert_eq!(
+            statuses(&report),
+            [
+                ("passingTest", Some(TestStatus::Pass)),
+                ("failingTest", Some(TestStatus::Fail)),
+                ("erroringTest", Some(TestStatus::Skipped)),
+                ("unseenTest", Some(TestStatus::Skipped)),
+            ]
+        );
+        assert_eq!(report.skipped().count(), 2);
+        assert_eq!(report.failure_counts(), (1, 0));
+    }
+
+    #[test]
+    fn writes_junit_xml() {
+        let mut report = report();
+        report.cases[0].output = "Hello <app> & \u{1b}[1mworld\u{1b}[0m\n".to_string();
+        report.apply_filter(&TestFilter::parse("*ingTest").unwrap());
+        for case in &mut report.cases {
+            case.duration = Duration::from_millis(250);
+        }
+
+        let mut xml = Vec::new();
+        report.write_junit_to(&mut xml, "my_app \"tests\"").unwrap();
+
+        assert_eq!(
+            String::from_utf8(xml).unwrap(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="my_app &quot;tests&quot;" tests="4" failures="1" errors="1" skipped="1" time="1.000">
+    <testcase name="passingTest" classname="my_app &quot;tests&quot;" time="0.250">
+      <system-out>Hello &lt;app&gt; &amp; [1mworld[0m
+</system-out>
+    </testcase>
+    <testcase name="failingTest" classname="my_app &quot;tests&quot;" time="0.250">
+      <failure message="ASSERTION FAILED">ASSERTION FAILED
+</failure>
+    </testcase>
+    <testcase name="erroringTest" classname="my_app &quot;tests&quot;" time="0.250">
+      <error message="Error: Unhandled Exception">Error: Unhandled Exception
+Details: 'Failed invoking &lt;symbol&gt;'
+</error>
+    </testcase>
+    <testcase name="unseenTest" classname="my_app &quot;tests&quot;" time="0.250">
+      <skipped />
+    </testcase>
+  </testsuite>
+</testsuites>"#
+        );
+    }
+}
@@ -1,18 +1,52 @@
+mod activity;
+mod crash;
+mod junit;
+mod log_file;
+mod scenario;
+mod watch;
+
+pub use activity::{load_activity, Sample};
+pub use crash::{Crash, CrashDetector};
+pub use junit::{TestCase, TestFilter, TestReport, TestStatus};
+pub use log_file::LogFile;
+pub use scenario::{Scenario, ScenarioStep};
+pub use watch::FileWatcher;
+
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Args, Parser, ValueEnum};
+use debug_xml::DebugInfo;
+use std::collections::VecDeque;
+use std::cell::OnceCell;
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::process;
 use std::process::Stdio;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use regex::Regex;
+use serde::Serialize;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::time::timeout;
 
 const SHELL_RETRY_DELAY_MS: u64 = 250;
 
-#[derive(Parser, Clone)]
-pub struct Args {
+/// How long a closed shell is given to exit before it is killed.
+const SHELL_CLOSE_TIMEOUT_MS: u64 = 1000;
+
+/// How often `simulate --watch` checks the app files for changes.
+const WATCH_INTERVAL_MS: u64 = 250;
+
+/// Number of lines of shell output kept for diagnosing a step that failed.
+const DIAGNOSTIC_LINES: usize = 20;
+
+/// Time left for reporting a timed out test run before Bazel kills the test.
+const TEST_TIMEOUT_MARGIN_SECS: u64 = 10;
+
+/// Command line arguments shared by every simulator tool: the app to run,
+/// how long to wait for the shell, and how to print the logs.
+#[derive(Args, Clone, Debug)]
+#[command(about = None, long_about = None)]
+pub struct SimulatorArgs {
     pub simulator_path: PathBuf,
     pub shell_path: PathBuf,
     pub application_id: String,
@@ -20,12 +54,205 @@ pub struct Args {
     pub debug_xml_path: PathBuf,
     pub settings_json_path: PathBuf,
     pub device: String,
+    /// Seconds to wait for the Connect IQ shell to accept a connection.
+    /// Timeouts of 0 wait forever.
+    #[arg(long, value_name = "SECONDS", default_value_t = 120)]
+    pub connect_timeout: u64,
+    /// Seconds to wait for each file to be pushed to the simulator.
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub push_timeout: u64,
+    /// Seconds to wait for the shell to connect to the simulator.
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub shell_connected_timeout: u64,
+    /// Seconds to wait for the simulator to start the device.
+    #[arg(long, value_name = "SECONDS", default_value_t = 120)]
+    pub device_started_timeout: u64,
+    /// Also write every log line, with the channel it came from and the time
+    /// it arrived, to this file as JSON lines.
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// Only print log lines from these channels. Defaults to every channel
+    /// but `test`. Does not affect `--log-file`.
+    #[arg(long, value_name = "CHANNELS", value_delimiter = ',')]
+    pub filter: Vec<Channel>,
+}
+
+impl SimulatorArgs {
+    /// Returns the build given by the positional arguments.
+    pub fn device_build(&self) -> DeviceBuild {
+        DeviceBuild {
+            device: self.device.clone(),
+            prg_path: self.prg_path.clone(),
+            debug_xml_path: self.debug_xml_path.clone(),
+            settings_json_path: self.settings_json_path.clone(),
+        }
+    }
+
+    /// Returns how long to wait for `step`, or `None` to wait forever.
+    pub fn timeout(&self, step: &Step) -> Option<Duration> {
+        let seconds = match step {
+            Step::Connect => self.connect_timeout,
+            Step::Push(_) => self.push_timeout,
+            Step::ShellConnected => self.shell_connected_timeout,
+            Step::DeviceStarted(_) => self.device_started_timeout,
+            // `test` and scenarios give the timeouts of these steps.
+            Step::TestCompletion(_) | Step::LogMarker(_) => 0,
+        };
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+}
+
+/// Options of [`simulate`].
+#[derive(Args, Clone, Debug)]
+#[command(about = None, long_about = None)]
+pub struct SimulateOptions {
+    /// Keep the simulator running, and push the app again and restart it
+    /// whenever its files change.
+    #[arg(long)]
+    pub watch: bool,
+}
+
+/// Options of [`test`].
+#[derive(Args, Clone, Debug)]
+#[command(about = None, long_about = None)]
+pub struct TestOptions {
+    /// Only report the results of tests whose names match this
+    /// comma-separated list of patterns. Defaults to Bazel's `--test_filter`.
+    #[arg(long)]
+    pub test_filter: Option<String>,
+    /// Seconds to wait for all tests to complete. Defaults to shortly before
+    /// Bazel's test timeout, or forever outside `bazel test`.
+    #[arg(long, value_name = "SECONDS")]
+    pub test_timeout: Option<u64>,
+}
+
+impl TestOptions {
+    /// Returns how long to wait for all tests to complete, or `None` to wait
+    /// forever.
+    pub fn timeout(&self) -> Option<Duration> {
+        // Bazel sets TEST_TIMEOUT to the seconds it allows the test.
+        let seconds = match self.test_timeout {
+            Some(seconds) => seconds,
+            None => std::env::var("TEST_TIMEOUT")
+                .ok()
+                .and_then(|timeout| timeout.parse::<u64>().ok())
+                .map_or(0, |timeout| {
+                    timeout.saturating_sub(TEST_TIMEOUT_MARGIN_SECS).max(1)
+                }),
+        };
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+}
+
+/// Options of [`smoke_test`].
+#[derive(Args, Clone, Debug)]
+#[command(about = None, long_about = None)]
+pub struct SmokeTestOptions {
+    /// Seconds to run the app for in a smoke test.
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub smoke_test_run_time: u64,
+}
+
+/// Options of [`scenario`].
+#[derive(Args, Clone, Debug)]
+#[command(about = None, long_about = None)]
+pub struct ScenarioOptions {
+    /// The scenario of input and expected logs to play, as a JSON file.
+    #[arg(long, value_name = "PATH")]
+    pub scenario: PathBuf,
+}
+
+/// Options of [`sweep`].
+#[derive(Args, Clone, Debug)]
+#[command(about = None, long_about = None)]
+pub struct SweepOptions {
+    /// Also run the app on these devices, one after another, given as
+    /// `<device>=<prg path>` with the debug XML and settings next to the PRG.
+    #[arg(long, value_name = "DEVICE=PRG")]
+    pub sweep: Vec<DeviceBuild>,
+    /// Seconds to run the app on each device of a sweep.
+    #[arg(long, value_name = "SECONDS", default_value_t = 5)]
+    pub sweep_run_time: u64,
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli<O: Args> {
+    #[command(flatten)]
+    simulator: SimulatorArgs,
+
+    #[command(flatten)]
+    options: O,
+}
+
+/// The source of a line of simulator output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Messages from the simulator, such as `deviceStarted`.
+    Simulator,
+    /// Messages from the simulated device.
+    Device,
+    /// Output of the app, such as `System.println`.
+    App,
+    /// Output of the app's tests.
+    Test,
+    /// Lines from none of the above.
+    Raw,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Channel::Simulator => "simulator",
+            Channel::Device => "device",
+            Channel::App => "app",
+            Channel::Test => "test",
+            Channel::Raw => "raw",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A step of a simulator session that waits for the shell.
+#[derive(Clone, Debug)]
+pub enum Step {
+    Connect,
+    /// Pushing the named file.
+    Push(String),
+    ShellConnected,
+    /// Starting the named device.
+    DeviceStarted(String),
+    /// Running tests, with the name of the test running, if known.
+    TestCompletion(Option<String>),
+    /// Waiting for the app to log the given text.
+    LogMarker(String),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Connect => write!(f, "connecting to the Connect IQ shell"),
+            Step::Push(name) => write!(f, "pushing {} to the simulator", name),
+            Step::ShellConnected => write!(f, "waiting for the shell to connect to the simulator"),
+            Step::DeviceStarted(device) => write!(f, "waiting for the simulator to start {}", device),
+            Step::TestCompletion(Some(test)) => write!(f, "running tests (test {} was running)", test),
+            Step::TestCompletion(None) => write!(f, "running tests"),
+            Step::LogMarker(marker) => write!(f, "waiting for the app to log '{}'", marker),
+        }
+    }
 }
 
 pub struct Shell {
-    _process: Child,
+    process: Child,
     pub stdout_reader: BufReader<tokio::process::ChildStdout>,
     pub stdin_writer: tokio::process::ChildStdin,
+    /// The last [`DIAGNOSTIC_LINES`] complete lines read from the shell.
+    recent_lines: VecDeque<String>,
+    /// The line being read from the shell.
+    partial_line: Vec<u8>,
+    /// The part of a line read by an interrupted [`Shell::next_line`].
+    line_buffer: Vec<u8>,
 }
 
 impl Shell {
@@ -48,12 +275,130 @@ impl Shell {
         let stdout_reader = BufReader::new(stdout);
 
         Ok(Self {
-            _process: process,
+            process,
             stdout_reader,
             stdin_writer: stdin,
+            recent_lines: VecDeque::with_capacity(DIAGNOSTIC_LINES),
+            partial_line: Vec::new(),
+            line_buffer: Vec::new(),
         })
     }
 
+    /// Starts a shell and waits for its prompt, retrying until the simulator
+    /// accepts the connection.
+    pub async fn connect(args: &SimulatorArgs) -> Result<Self> {
+        let connect_timeout = args.timeout(&Step::Connect);
+        let connect_started = Instant::now();
+        loop {
+            let last_error = match Shell::new(&args.shell_path).await {
+                Ok(mut s) => {
+                    // Try to get the initial prompt
+                    if s.wait_for(":>", Some(Duration::from_millis(SHELL_RETRY_DELAY_MS))).await.is_ok() {
+                        return Ok(s);
+                    }
+                    s.diagnostics()
+                }
+                Err(e) => format!("{:#}", e),
+            };
+            if let Some(duration) = connect_timeout {
+                if connect_started.elapsed() >= duration {
+                    return Err(anyhow::anyhow!(
+                        "Timed out after {}s {}\nLast attempt: {}",
+                        duration.as_secs(),
+                        Step::Connect,
+                        last_error
+                    ));
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(SHELL_RETRY_DELAY_MS)).await;
+        }
+    }
+
+    /// Connects a shell to the simulator, pushes `files`, switches the shell
+    /// to the `ciq` protocol and opens `device`, ready to start the app.
+    pub async fn open_session(
+        args: &SimulatorArgs,
+        device: &str,
+        files: &[AppFile],
+    ) -> Result<Self> {
+        let mut shell = Shell::connect(args).await?;
+
+        for file in files {
+            shell.push(file, args).await?;
+        }
+
+        // Connect to CIQ
+        shell.send("ciq").await?;
+        shell
+            .wait_for_step(&Step::ShellConnected, "[1][0]shellConnected", args)
+            .await?;
+
+        // Open Device
+        shell.open_device(device, args).await?;
+        Ok(shell)
+    }
+
+    /// Closes this shell's connection, then opens a new session that pushes
+    /// `files` and opens `device`.
+    ///
+    /// The shell protocol is undocumented, and nothing shows that the
+    /// simulator accepts a second shell while one is connected, or a `push`
+    /// once `ciq` has been sent. So the new session is set up only once this
+    /// one has ended, exactly as when a tool connects to a simulator that is
+    /// already running.
+    pub async fn reopen(
+        self,
+        args: &SimulatorArgs,
+        device: &str,
+        files: &[AppFile],
+    ) -> Result<Self> {
+        self.close().await;
+        Shell::open_session(args, device, files).await
+    }
+
+    /// Ends the shell's input, and waits for it to exit, killing it if it
+    /// does not exit in time.
+    pub async fn close(self) {
+        let Shell {
+            mut process,
+            stdin_writer,
+            ..
+        } = self;
+        drop(stdin_writer);
+        let exited = timeout(Duration::from_millis(SHELL_CLOSE_TIMEOUT_MS), process.wait()).await;
+        if exited.is_err() {
+            let _ = process.kill().await;
+        }
+    }
+
+    /// Pushes `file` to the simulator. Only valid before `ciq` is sent.
+    pub async fn push(&mut self, file: &AppFile, args: &SimulatorArgs) -> Result<()> {
+        self.send(&format!(
+            "push \"{}\" \"{}\"",
+            file.local_path.display(),
+            file.device_path
+        ))
+        .await?;
+        let name = file
+            .local_path
+            .file_name()
+            .map_or_else(|| file.device_path.clone(), |name| name.to_string_lossy().into_owned());
+        let step = Step::Push(name);
+        self.wait_for_step(&step, "File pushed successfully", args).await?;
+        self.wait_for_step(&step, ":>", args).await
+    }
+
+    /// Opens `device` in the simulator. Only valid after `ciq` is sent.
+    pub async fn open_device(&mut self, device: &str, args: &SimulatorArgs) -> Result<()> {
+        self.send(&format!("[1][0]openDevice {}", device)).await?;
+        self.wait_for_step(
+            &Step::DeviceStarted(device.to_string()),
+            &format!("[1][0]deviceStarted {}", device),
+            args,
+        )
+        .await
+    }
+
     pub async fn send(&mut self, command: &str) -> Result<()> {
 
         self.stdin_writer
@@ -82,6 +427,7 @@ impl Shell {
                 }
 
                 buffer.push(byte[0]);
+                self.record(byte[0]);
 
 
                 // Check if buffer ends with the phrase
@@ -105,14 +451,192 @@ impl Shell {
 
         Ok(())
     }
+
+    /// Waits for `phrase` to complete `step`, failing with the recent output
+    /// of the shell if it does not appear in time.
+    pub async fn wait_for_step(
+        &mut self,
+        step: &Step,
+        phrase: &str,
+        args: &SimulatorArgs,
+    ) -> Result<()> {
+        let timeout_duration = args.timeout(step);
+        match timeout_duration {
+            Some(duration) => match timeout(duration, self.wait_for(phrase, None)).await {
+                Ok(result) => result.map_err(|e| self.step_error(step, e)),
+                Err(_) => Err(self.timeout_error(step, duration)),
+            },
+            None => self.wait_for(phrase, None).await.map_err(|e| self.step_error(step, e)),
+        }
+    }
+
+    /// Reads the next line of output, without its line ending, or `None` at
+    /// the end of the output.
+    ///
+    /// Cancellation safe: a partly read line is kept for the next call.
+    pub async fn next_line(&mut self) -> Result<Option<String>> {
+        let n = self
+            .stdout_reader
+            .read_until(b'\n', &mut self.line_buffer)
+            .await?;
+        if n == 0 && self.line_buffer.is_empty() {
+            return Ok(None);
+        }
+        let bytes = std::mem::take(&mut self.line_buffer);
+        for &byte in &bytes {
+            self.record(byte);
+        }
+        let line = String::from_utf8_lossy(&bytes);
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+
+    /// Returns an error reporting that `step` timed out after `duration`.
+    pub fn timeout_error(&self, step: &Step, duration: Duration) -> anyhow::Error {
+        anyhow::anyhow!(
+            "Timed out after {}s {}\n{}",
+            duration.as_secs(),
+            step,
+            self.diagnostics()
+        )
+    }
+
+    fn step_error(&self, step: &Step, error: anyhow::Error) -> anyhow::Error {
+        anyhow::anyhow!("Failed {}: {}\n{}", step, error, self.diagnostics())
+    }
+
+    /// Returns the last lines of output read from the shell.
+    fn diagnostics(&self) -> String {
+        let mut lines: Vec<String> = self.recent_lines.iter().cloned().collect();
+        if !self.partial_line.is_empty() {
+            lines.push(String::from_utf8_lossy(&self.partial_line).into_owned());
+        }
+        if lines.is_empty() {
+            return "The shell printed nothing.".to_string();
+        }
+        format!(
+            "Last {} line(s) of shell output:\n{}",
+            lines.len(),
+            lines
+                .iter()
+                .map(|line| format!("  {}", line))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+
+    fn record(&mut self, byte: u8) {
+        if byte != b'\n' {
+            self.partial_line.push(byte);
+            return;
+        }
+        if self.recent_lines.len() == DIAGNOSTIC_LINES {
+            self.recent_lines.pop_front();
+        }
+        let line = String::from_utf8_lossy(&self.partial_line)
+            .trim_end_matches('\r')
+            .to_string();
+        self.recent_lines.push_back(line);
+        self.partial_line.clear();
+    }
+}
+
+/// The outputs of a `ciq_device_build` for one device.
+#[derive(Clone, Debug)]
+pub struct DeviceBuild {
+    pub device: String,
+    pub prg_path: PathBuf,
+    pub debug_xml_path: PathBuf,
+    pub settings_json_path: PathBuf,
+}
+
+impl std::str::FromStr for DeviceBuild {
+    type Err = String;
+
+    /// Parses `<device>=<prg path>`, finding the debug XML and settings next
+    /// to the PRG under the names `ciq_device_build` gives them.
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        let (device, prg) = text
+            .split_once('=')
+            .ok_or_else(|| format!("expected <device>=<prg path>, got '{}'", text))?;
+        let prefix = prg.strip_suffix(".prg").unwrap_or(prg);
+        Ok(DeviceBuild {
+            device: device.to_string(),
+            prg_path: PathBuf::from(prg),
+            debug_xml_path: PathBuf::from(format!("{}.prg.debug.xml", prefix)),
+            settings_json_path: PathBuf::from(format!("{}-settings.json", prefix)),
+        })
+    }
+}
+
+/// A file of the app, and where the simulator expects it.
+#[derive(Clone, Debug)]
+pub struct AppFile {
+    pub local_path: PathBuf,
+    pub device_path: String,
+}
+
+/// Returns the files pushed to the simulator before starting the app: its
+/// settings, debug XML if built, and executable.
+pub fn app_files(args: &DeviceBuild) -> Result<Vec<AppFile>> {
+    let mut files = Vec::new();
+
+    // Settings
+    let settings_name = args
+        .settings_json_path
+        .file_name()
+        .context("Invalid settings path")?
+        .to_string_lossy();
+
+    let settings_suffix = "-settings.json";
+    let settings_adjusted_name = if settings_name.ends_with(settings_suffix) {
+        let prefix_len = settings_name.len() - settings_suffix.len();
+        let prefix = &settings_name[..prefix_len];
+        format!("{}{}", prefix.to_uppercase(), &settings_name[prefix_len..])
+    } else {
+        settings_name.to_string()
+    };
+    files.push(AppFile {
+        local_path: args.settings_json_path.clone(),
+        device_path: format!("0:/GARMIN/Settings/{}", settings_adjusted_name),
+    });
+
+    // Debug XML
+    if args.debug_xml_path.exists() {
+        let debug_xml_name = args
+            .debug_xml_path
+            .file_name()
+            .context("Invalid Debug XML path")?
+            .to_string_lossy();
+        files.push(AppFile {
+            local_path: args.debug_xml_path.clone(),
+            device_path: format!("0:/GARMIN/Debug/{}", debug_xml_name.to_uppercase()),
+        });
+    }
+
+    // PRG
+    let prg_name = args
+        .prg_path
+        .file_name()
+        .context("Invalid PRG path")?
+        .to_string_lossy();
+    files.push(AppFile {
+        local_path: args.prg_path.clone(),
+        device_path: format!("0:/GARMIN/APPS/{}", prg_name),
+    });
+
+    Ok(files)
 }
 
-pub fn run_simulator_operation<F, Fut>(operation: F)
+/// Starts the simulator, pushes the app given on the command line and opens
+/// its device, then runs `operation` with the shell and the options `O` of
+/// the tool, also parsed from the command line.
+pub fn run_simulator_operation<O, F, Fut>(operation: F)
 where
-    F: FnOnce(Shell, Args) -> Fut,
+    O: Args,
+    F: FnOnce(Shell, SimulatorArgs, O) -> Fut,
     Fut: std::future::Future<Output = Result<()>>,
 {
-    let args = Args::parse();
+    let Cli { simulator: args, options } = Cli::<O>::parse();
     
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -128,92 +652,10 @@ where
             .spawn()
             .context("Failed to spawn simulator")?;
 
-        // Retry connecting to shell until successful
-        let mut shell = loop {
-            if let Ok(mut s) = Shell::new(&args.shell_path).await {
-                // Try to get the initial prompt
-                if s.wait_for(":>", Some(Duration::from_millis(SHELL_RETRY_DELAY_MS))).await.is_ok() {
-                    break s;
-                }
-            }
-            tokio::time::sleep(Duration::from_millis(SHELL_RETRY_DELAY_MS)).await;
-        };
+        let files = app_files(&args.device_build())?;
+        let shell = Shell::open_session(&args, &args.device, &files).await?;
 
-        // Push settings
-        let settings_name = args
-            .settings_json_path
-            .file_name()
-            .context("Invalid settings path")?
-            .to_string_lossy();
-        
-        let settings_suffix = "-settings.json";
-        let settings_adjusted_name = if settings_name.ends_with(settings_suffix) {
-            let prefix_len = settings_name.len() - settings_suffix.len();
-            let prefix = &settings_name[..prefix_len];
-            format!("{}{}", prefix.to_uppercase(), &settings_name[prefix_len..])
-        } else {
-            settings_name.to_string()
-        };
-
-        shell
-            .send(&format!(
-                "push \"{}\" \"0:/GARMIN/Settings/{}\"",
-                args.settings_json_path.display(),
-                settings_adjusted_name
-            ))
-            .await?;
-        shell.wait_for("File pushed successfully", None).await?;
-        shell.wait_for(":>", None).await?;
-
-        // Push Debug XML
-        if args.debug_xml_path.exists() {
-            let debug_xml_name = args
-                .debug_xml_path
-                .file_name()
-                .context("Invalid Debug XML path")?
-                .to_string_lossy();
-            
-            shell
-                .send(&format!(
-                    "push \"{}\" \"0:/GARMIN/Debug/{}\"",
-                    args.debug_xml_path.display(),
-                    debug_xml_name.to_uppercase()
-                ))
-                .await?;
-            shell.wait_for("File pushed successfully", None).await?;
-            shell.wait_for(":>", None).await?;
-        }
-
-        // Push PRG
-        let prg_name = args
-            .prg_path
-            .file_name()
-            .context("Invalid PRG path")?
-            .to_string_lossy();
-        
-        shell
-            .send(&format!(
-                "push \"{}\" \"0:/GARMIN/APPS/{}\"",
-                args.prg_path.display(),
-                prg_name
-            ))
-            .await?;
-        shell.wait_for("File pushed successfully", None).await?;
-        shell.wait_for(":>", None).await?;
-
-        // Connect to CIQ
-        shell.send("ciq").await?;
-        shell.wait_for("[1][0]shellConnected", None).await?;
-
-        // Open Device
-        shell
-            .send(&format!("[1][0]openDevice {}", args.device))
-            .await?;
-        shell
-            .wait_for(&format!("[1][0]deviceStarted {}", args.device), None)
-            .await?;
-
-        let result = operation(shell, args.clone()).await;
+        let result = operation(shell, args, options).await;
 
         // Cleanup (optional, as OS will clean up, but good practice)
         let _ = simulator_process.kill();
@@ -227,7 +669,13 @@ where
     }
 }
 
-pub async fn simulate(mut shell: Shell, args: Args) -> Result<()> {
+pub async fn simulate(
+    mut shell: Shell,
+    args: SimulatorArgs,
+    options: SimulateOptions,
+) -> Result<()> {
+    let mut printer = LogPrinter::new(&args)?;
+
     // Start App
     let formatted_app_id = args.application_id.replace("-", "").to_uppercase();
     shell
@@ -245,9 +693,128 @@ pub async fn simulate(mut shell: Shell, args: Args) -> Result<()> {
             .output();
     }
 
-    let mut on_new_line = true;
-    stream_logs(shell, &formatted_app_id, |log_line| {
-        if matches!(log_line, LogLine::Test(_)) {
+    let mut watcher = options.watch.then(|| {
+        FileWatcher::new([
+            args.prg_path.clone(),
+            args.settings_json_path.clone(),
+            args.debug_xml_path.clone(),
+        ])
+    });
+
+    loop {
+        let streamed = stream_logs(&mut shell, &formatted_app_id, |log_line| printer.print(log_line));
+        let Some(watcher) = &mut watcher else {
+            streamed.await?;
+            break;
+        };
+
+        // Streaming is interrupted to check for changes, which loses nothing
+        // as reading lines is cancellation safe.
+        if let Ok(result) = timeout(Duration::from_millis(WATCH_INTERVAL_MS), streamed).await {
+            result?;
+            break;
+        }
+        let changed = watcher.changed();
+        if !changed.is_empty() {
+            shell = reload(shell, &args, &changed, &formatted_app_id, &mut printer).await?;
+        }
+    }
+
+    printer.finish()
+}
+
+/// Pushes the `changed` app files to the simulator through a new session,
+/// and restarts the app, returning the new session's shell.
+async fn reload(
+    shell: Shell,
+    args: &SimulatorArgs,
+    changed: &[PathBuf],
+    formatted_app_id: &str,
+    printer: &mut LogPrinter,
+) -> Result<Shell> {
+    let files: Vec<AppFile> = app_files(&args.device_build())?
+        .into_iter()
+        .filter(|file| changed.contains(&file.local_path))
+        .collect();
+    let names: Vec<String> = files
+        .iter()
+        .filter_map(|file| file.local_path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    printer.notice(&format!("[WATCH] Reloading {}", names.join(", ")));
+    if changed.contains(&args.debug_xml_path) {
+        printer.use_debug_xml(&args.debug_xml_path);
+    }
+
+    let mut shell = shell.reopen(args, &args.device, &files).await?;
+    shell
+        .send(&format!("[2][0]startApp {}", formatted_app_id))
+        .await?;
+    Ok(shell)
+}
+
+/// Prints the logs of `simulate`, writes them to `--log-file`, and reports
+/// the errors they show.
+struct LogPrinter {
+    filter: Vec<Channel>,
+    log_file: Option<LogFile>,
+    log_file_error: Option<anyhow::Error>,
+    on_new_line: bool,
+    crash_detector: CrashDetector,
+    /// The debug XML with which to resolve the PCs of errors.
+    debug_xml_path: PathBuf,
+    /// The debug XML, loaded for the first error reported with it.
+    debug_info: OnceCell<Option<DebugInfo>>,
+    /// The errors reported so far.
+    crashes: Vec<Crash>,
+}
+
+impl LogPrinter {
+    fn new(args: &SimulatorArgs) -> Result<Self> {
+        Ok(Self {
+            filter: args.filter.clone(),
+            log_file: args.log_file.as_deref().map(LogFile::create).transpose()?,
+            log_file_error: None,
+            on_new_line: true,
+            crash_detector: CrashDetector::new(),
+            debug_xml_path: args.debug_xml_path.clone(),
+            debug_info: OnceCell::new(),
+            crashes: Vec::new(),
+        })
+    }
+
+    /// Resolves the PCs of later errors with the debug XML at `path`, such as
+    /// when it has been rebuilt or another device's build is run.
+    fn use_debug_xml(&mut self, path: &Path) {
+        self.debug_xml_path = path.to_path_buf();
+        self.debug_info.take();
+    }
+
+    /// Handles `log_line`, returning whether to stop streaming.
+    fn print(&mut self, log_line: LogLine) -> bool {
+        // Test failures are reported by `test` itself.
+        if !matches!(log_line, LogLine::Test(_)) {
+            for crash in self.crash_detector.feed(log_line.text()) {
+                self.report(crash);
+            }
+        }
+
+        // The empty rest of the line on which the handshake ended is not
+        // worth logging.
+        let logged = !matches!(log_line, LogLine::Raw(""));
+        if let Some(log_file) = self.log_file.as_mut().filter(|_| logged) {
+            if let Err(e) = log_file.write(log_line.channel(), log_line.text()) {
+                self.log_file_error = Some(e);
+                return true;
+            }
+        }
+
+        let shown = if self.filter.is_empty() {
+            !matches!(log_line, LogLine::Test(_))
+        } else {
+            self.filter.contains(&log_line.channel())
+        };
+        if !shown {
             return false;
         }
 
@@ -255,39 +822,388 @@ pub async fn simulate(mut shell: Shell, args: Args) -> Result<()> {
             LogLine::Simulator(c) => format!("[SIMULATOR] {}\n", c),
             LogLine::Device(c) => format!("[DEVICE] {}\n", c),
             LogLine::App(c) => c.to_string(),
+            LogLine::Test(c) => c.to_string(),
             LogLine::Raw(c) => c.to_string(),
-            LogLine::Test(_) => unreachable!(),
         };
 
-        if !matches!(log_line, LogLine::App(_)) && !on_new_line {
-            println!();
+        if !matches!(log_line, LogLine::App(_) | LogLine::Test(_)) {
+            self.start_line();
         }
 
         print!("{}", output);
         std::io::Write::flush(&mut std::io::stdout()).ok();
 
-        on_new_line = output.ends_with('\n');
+        self.on_new_line = output.ends_with('\n');
         false
-    }).await
+    }
+
+    /// Prints `crash`, with its PCs resolved, and records it.
+    fn report(&mut self, crash: Crash) {
+        let debug_info = self
+            .debug_info
+            .get_or_init(|| crash::load_debug_info(&self.debug_xml_path));
+        for line in crash.report(debug_info.as_ref()) {
+            self.notice(&format!("[CRASH] {}", line));
+        }
+        self.crashes.push(crash);
+    }
+
+    /// Reports an error still being reported when the logs end, and fails if
+    /// the app reported any errors or the log file could not be written.
+    fn finish(mut self) -> Result<()> {
+        self.report_pending();
+        if let Some(e) = self.log_file_error {
+            return Err(e);
+        }
+        match self.crashes.len() {
+            0 => Ok(()),
+            count => Err(anyhow::anyhow!("The app reported {} error(s)", count)),
+        }
+    }
+
+    fn report_pending(&mut self) {
+        if let Some(crash) = self.crash_detector.finish() {
+            self.report(crash);
+        }
+    }
+
+    /// Prints a message of this tool's own on a line of its own.
+    fn notice(&mut self, message: &str) {
+        self.start_line();
+        println!("{}", message);
+        self.on_new_line = true;
+    }
+
+    fn start_line(&mut self) {
+        if !self.on_new_line {
+            println!();
+        }
+    }
+}
+
+pub async fn sweep(mut shell: Shell, args: SimulatorArgs, options: SweepOptions) -> Result<()> {
+    let formatted_app_id = args.application_id.replace("-", "").to_uppercase();
+    let mut printer = LogPrinter::new(&args)?;
+    let mut results = Vec::new();
+
+    // The device of the positional arguments was pushed and opened by the
+    // handshake.
+    let builds: Vec<DeviceBuild> = std::iter::once(args.device_build())
+        .chain(options.sweep.iter().cloned())
+        .collect();
+    for (index, build) in builds.iter().enumerate() {
+        let device = &build.device;
+        printer.notice(&format!("[SWEEP] {}", device));
+        printer.use_debug_xml(&build.debug_xml_path);
+        let mut result = SweepResult {
+            device: device.clone(),
+            errors: Vec::new(),
+            failure: None,
+        };
+
+        // Each build is pushed just before it runs, through a new session
+        // that opens its device.
+        if index > 0 {
+            let files = match app_files(build) {
+                Ok(files) => files,
+                Err(e) => {
+                    result.failure = Some(format!("{:#}", e));
+                    results.push(result);
+                    continue;
+                }
+            };
+            shell = match shell.reopen(&args, device, &files).await {
+                Ok(shell) => shell,
+                Err(e) => {
+                    result.failure = Some(format!("{:#}", e));
+                    results.push(result);
+                    break;
+                }
+            };
+        }
+
+        let stopped = match shell
+            .send(&format!("[2][0]startApp {}", formatted_app_id))
+            .await
+        {
+            Ok(()) => {
+                let streamed =
+                    stream_logs(&mut shell, &formatted_app_id, |log_line| printer.print(log_line));
+                match timeout(Duration::from_secs(options.sweep_run_time), streamed).await {
+                    Ok(Ok(())) => Some(printer.log_file_error.take().unwrap_or_else(|| {
+                        anyhow::anyhow!("The simulator stopped while running on {}", device)
+                    })),
+                    Ok(Err(e)) => Some(e),
+                    Err(_) => None,
+                }
+            }
+            Err(e) => Some(e),
+        };
+        printer.report_pending();
+        result.errors = std::mem::take(&mut printer.crashes)
+            .into_iter()
+            .map(|crash| crash.error)
+            .collect();
+        result.failure = stopped.map(|e| format!("{:#}", e));
+        let stopped = result.failure.is_some();
+        results.push(result);
+        if stopped {
+            break;
+        }
+    }
+
+    // The devices after one on which the simulator stopped, or could not be
+    // reached again, are not run.
+    for build in &builds[results.len()..] {
+        results.push(SweepResult {
+            device: build.device.clone(),
+            errors: Vec::new(),
+            failure: Some("Not run, as the simulator stopped".to_string()),
+        });
+    }
+
+    printer.notice("");
+    print_sweep_summary(&results);
+
+    let failed = results
+        .iter()
+        .filter(|result| result.failure.is_some() || !result.errors.is_empty())
+        .count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} devices failed",
+            failed,
+            results.len()
+        ));
+    }
+    Ok(())
+}
+
+/// The outcome of running the app on one device of a sweep.
+struct SweepResult {
+    device: String,
+    /// The errors the app reported while it ran.
+    errors: Vec<String>,
+    /// Why the app could not be run on the device, if it could not.
+    failure: Option<String>,
+}
+
+/// Prints a table of the outcome on each device, with the first error or
+/// failure, if any.
+fn print_sweep_summary(results: &[SweepResult]) {
+    let mut rows = vec![[
+        "DEVICE".to_string(),
+        "STATUS".to_string(),
+        "ERRORS".to_string(),
+        "DETAILS".to_string(),
+    ]];
+    for result in results {
+        let status = if result.failure.is_some() {
+            "FAILED"
+        } else if !result.errors.is_empty() {
+            "ERRORS"
+        } else {
+            "OK"
+        };
+        let details = result
+            .failure
+            .as_deref()
+            .or(result.errors.first().map(String::as_str))
+            .and_then(|details| details.lines().next())
+            .unwrap_or("");
+        rows.push([
+            result.device.clone(),
+            status.to_string(),
+            result.errors.len().to_string(),
+            details.to_string(),
+        ]);
+    }
+
+    let mut widths = [0; 3];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in &rows {
+        println!(
+            "{:<w0$}  {:<w1$}  {:>w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+    }
+}
+
+pub async fn smoke_test(
+    mut shell: Shell,
+    args: SimulatorArgs,
+    options: SmokeTestOptions,
+) -> Result<()> {
+    let mut printer = LogPrinter::new(&args)?;
+
+    // Start App
+    let formatted_app_id = args.application_id.replace("-", "").to_uppercase();
+    shell
+        .send(&format!("[2][0]startApp {}", formatted_app_id))
+        .await?;
+
+    let run_time = Duration::from_secs(options.smoke_test_run_time);
+    let streamed = stream_logs(&mut shell, &formatted_app_id, |log_line| printer.print(log_line));
+    let stopped = match timeout(run_time, streamed).await {
+        Ok(result) => {
+            result?;
+            true
+        }
+        Err(_) => false,
+    };
+
+    // Errors reported by the app explain the simulator stopping early.
+    printer.finish()?;
+    if stopped {
+        return Err(anyhow::anyhow!(
+            "The simulator stopped before the app had run for {} seconds",
+            options.smoke_test_run_time
+        ));
+    }
+    println!(
+        "The app ran for {} seconds without reporting errors",
+        options.smoke_test_run_time
+    );
+    Ok(())
+}
+
+pub async fn scenario(
+    mut shell: Shell,
+    args: SimulatorArgs,
+    options: ScenarioOptions,
+) -> Result<()> {
+    let scenario = Scenario::load(&working_directory_path(&options.scenario))?;
+    let mut printer = LogPrinter::new(&args)?;
+
+    // Start App
+    let formatted_app_id = args.application_id.replace("-", "").to_uppercase();
+    shell
+        .send(&format!("[2][0]startApp {}", formatted_app_id))
+        .await?;
+
+    // App lines logged since the last `expect_log` step matched.
+    let mut unmatched = Vec::new();
+    for (index, step) in scenario.steps.iter().enumerate() {
+        printer.notice(&format!(
+            "[SCENARIO] Step {}/{}: {}",
+            index + 1,
+            scenario.steps.len(),
+            step
+        ));
+        play_step(&mut shell, &scenario, step, &formatted_app_id, &mut printer, &mut unmatched)
+            .await
+            .with_context(|| format!("Scenario step {} ({}) failed", index + 1, step))?;
+    }
+
+    printer.finish()?;
+    println!("Completed all {} scenario steps", scenario.steps.len());
+    Ok(())
+}
+
+async fn play_step(
+    shell: &mut Shell,
+    scenario: &Scenario,
+    step: &ScenarioStep,
+    formatted_app_id: &str,
+    printer: &mut LogPrinter,
+    unmatched: &mut Vec<String>,
+) -> Result<()> {
+    match step {
+        ScenarioStep::Send(command) => shell.send(command).await,
+        ScenarioStep::Wait(seconds) => {
+            let duration = Duration::try_from_secs_f64(*seconds)
+                .with_context(|| format!("Invalid number of seconds to wait: {}", seconds))?;
+            let streamed = stream_logs(shell, formatted_app_id, |log_line| {
+                if let LogLine::App(text) = log_line {
+                    unmatched.push(text.to_string());
+                }
+                printer.print(log_line)
+            });
+            match timeout(duration, streamed).await {
+                Ok(result) => {
+                    result?;
+                    Err(printer
+                        .log_file_error
+                        .take()
+                        .unwrap_or_else(|| anyhow::anyhow!("The simulator stopped")))
+                }
+                Err(_) => Ok(()),
+            }
+        }
+        ScenarioStep::ExpectLog(text) => {
+            if let Some(position) = unmatched.iter().position(|line| line.contains(text.as_str())) {
+                unmatched.drain(..=position);
+                return Ok(());
+            }
+            unmatched.clear();
+
+            let step = Step::LogMarker(text.clone());
+            let duration = Duration::from_secs(scenario.timeout);
+            let mut found = false;
+            let streamed = stream_logs(shell, formatted_app_id, |log_line| {
+                found = matches!(log_line, LogLine::App(line) if line.contains(text.as_str()));
+                printer.print(log_line) || found
+            });
+            match timeout(duration, streamed).await {
+                Ok(result) => result?,
+                Err(_) => return Err(shell.timeout_error(&step, duration)),
+            }
+            if !found {
+                return Err(printer
+                    .log_file_error
+                    .take()
+                    .unwrap_or_else(|| anyhow::anyhow!("The simulator stopped {}", step)));
+            }
+            Ok(())
+        }
+    }
 }
 
-pub async fn test(mut shell: Shell, args: Args) -> Result<()> {
+/// Resolves `path` against the directory `bazel run` was started from, if
+/// run by Bazel.
+pub(crate) fn working_directory_path(path: &Path) -> PathBuf {
+    match std::env::var_os("BUILD_WORKING_DIRECTORY") {
+        Some(dir) if path.is_relative() => Path::new(&dir).join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+pub async fn test(mut shell: Shell, args: SimulatorArgs, options: TestOptions) -> Result<()> {
     // Start App (Run All Tests)
     let formatted_app_id = args.application_id.replace("-", "").to_uppercase();
     shell
         .send(&format!("[2][0]runAllTests {}", formatted_app_id))
         .await?;
 
-    let mut test_outcome = None;
+    // Bazel sets TESTBRIDGE_TEST_ONLY to the value of `--test_filter`.
+    let filter = options
+        .test_filter
+        .clone()
+        .or_else(|| std::env::var("TESTBRIDGE_TEST_ONLY").ok())
+        .and_then(|filter| TestFilter::parse(&filter));
+
+    let mut report = TestReport::new();
 
-    stream_logs(shell, &formatted_app_id, |log_line| {
+    let test_timeout = options.timeout();
+    let streamed = stream_logs(&mut shell, &formatted_app_id, |log_line| {
         match log_line {
             LogLine::App(message) => {
                 print!("{}", message);
+                report.feed_app_output(message);
             }
             LogLine::Test(message) => {
                 print!("{}", message);
-                test_outcome = Some(message.to_string());
+                report.feed_test_output(message);
             }
             LogLine::Simulator(message) => {
                 if message.contains("deviceTerminated") {
@@ -297,12 +1213,55 @@ pub async fn test(mut shell: Shell, args: Args) -> Result<()> {
             _ => {}
         }
         false
-    }).await?;
+    });
+    match test_timeout {
+        Some(duration) => match timeout(duration, streamed).await {
+            Ok(result) => result?,
+            Err(_) => {
+                let step = Step::TestCompletion(report.running().map(str::to_string));
+                return Err(shell.timeout_error(&step, duration));
+            }
+        },
+        None => streamed.await?,
+    }
 
-    if let Some(text) = test_outcome {
+    if let Some(filter) = &filter {
+        report.apply_filter(filter);
+        let skipped: Vec<&str> = report.skipped().map(|case| case.name.as_str()).collect();
+        if !skipped.is_empty() {
+            println!(
+                "Skipped {} test(s) not matching '{}': {}",
+                skipped.len(),
+                filter,
+                skipped.join(", ")
+            );
+        }
+    }
+
+    // Bazel sets XML_OUTPUT_FILE for `bazel test`, and reads per-test results
+    // from it.
+    if let Some(xml_output_file) = std::env::var_os("XML_OUTPUT_FILE") {
+        let suite_name = args
+            .prg_path
+            .file_stem()
+            .map_or_else(|| args.device.clone(), |stem| stem.to_string_lossy().into_owned());
+        report.write_junit(Path::new(&xml_output_file), &suite_name)?;
+    }
+
+    // With a filter, the summary line also counts the skipped tests, so the
+    // outcome is decided by the selected tests alone.
+    if let Some(filter) = &filter {
+        if report.skipped().count() == report.cases.len() {
+            return Err(anyhow::anyhow!("No tests match '{}'", filter));
+        }
+        let (failures, errors) = report.failure_counts();
+        if failures + errors > 0 {
+            return Err(not_all_passed(&report));
+        }
+    } else if let Some(text) = &report.summary {
         let re = Regex::new(r"PASSED \(passed=\d+, failed=0, errors=0\)").unwrap();
-        if !re.is_match(&text) {
-            return Err(anyhow::anyhow!("Not all tests passed"));
+        if !re.is_match(text) {
+            return Err(not_all_passed(&report));
         }
     } else {
             return Err(anyhow::anyhow!("No test outcome found"));
@@ -311,6 +1270,20 @@ pub async fn test(mut shell: Shell, args: Args) -> Result<()> {
     Ok(())
 }
 
+fn not_all_passed(report: &TestReport) -> anyhow::Error {
+    let failed: Vec<&str> = report
+        .cases
+        .iter()
+        .filter(|case| !matches!(case.status, Some(TestStatus::Pass | TestStatus::Skipped)))
+        .map(|case| case.name.as_str())
+        .collect();
+    if failed.is_empty() {
+        anyhow::anyhow!("Not all tests passed")
+    } else {
+        anyhow::anyhow!("Not all tests passed: {}", failed.join(", "))
+    }
+}
+
 #[derive(Clone, Copy)]
 enum LogLine<'a> {
     Simulator(&'a str),
@@ -320,16 +1293,37 @@ enum LogLine<'a> {
     Raw(&'a str),
 }
 
-async fn stream_logs<F>(shell: Shell, formatted_app_id: &str, mut callback: F) -> Result<()> 
+impl<'a> LogLine<'a> {
+    fn channel(&self) -> Channel {
+        match self {
+            LogLine::Simulator(_) => Channel::Simulator,
+            LogLine::Device(_) => Channel::Device,
+            LogLine::App(_) => Channel::App,
+            LogLine::Test(_) => Channel::Test,
+            LogLine::Raw(_) => Channel::Raw,
+        }
+    }
+
+    fn text(&self) -> &'a str {
+        match self {
+            LogLine::Simulator(text)
+            | LogLine::Device(text)
+            | LogLine::App(text)
+            | LogLine::Test(text)
+            | LogLine::Raw(text) => text,
+        }
+    }
+}
+
+async fn stream_logs<F>(shell: &mut Shell, formatted_app_id: &str, mut callback: F) -> Result<()> 
 where F: FnMut(LogLine) -> bool
 {
-    let mut lines = shell.stdout_reader.lines();
     let simulator_message_prefix = "[1][0]";
     let device_message_prefix = "[2][0]";
     let app_message_prefix = format!("[3][{}][0]", formatted_app_id);
     let test_message_prefix = format!("[4][{}][0]", formatted_app_id);
 
-    while let Some(line) = lines.next_line().await?.map(|l| l.replace("\\n", "\n")) {
+    while let Some(line) = shell.next_line().await?.map(|l| l.replace("\\n", "\n")) {
         let log_line = if let Some(stripped) = line.strip_prefix(simulator_message_prefix) {
             LogLine::Simulator(stripped)
         } else if let Some(stripped) = line.strip_prefix(device_message_prefix) {
@@ -0,0 +1,92 @@
+//! Capture of simulator sessions as JSON lines, one object per log line:
+//!
+//! ```text
+//! {"time":"2026-10-17T09:41:07.253Z","channel":"app","text":"Hello\n"}
+//! ```
+
+use crate::{working_directory_path, Channel};
+use anyhow::{Context, Result};
+use civil_date::format_date_time;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct LogFile {
+    path: PathBuf,
+    writer: LineWriter<File>,
+}
+
+impl LogFile {
+    /// Creates the log file at `path`, relative to the directory `bazel run`
+    /// was started from if run by Bazel.
+    pub fn create(path: &Path) -> Result<Self> {
+        let path = working_directory_path(path);
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create log file {}", path.display()))?;
+        Ok(Self {
+            path,
+            writer: LineWriter::new(file),
+        })
+    }
+
+    /// Appends `text` from `channel`, stamped with the current time. Each line
+    /// is written through, so that nothing is lost when the session is
+    /// interrupted.
+    pub fn write(&mut self, channel: Channel, text: &str) -> Result<()> {
+        let line = LogLine {
+            time: format_time(SystemTime::now()),
+            channel,
+            text,
+        };
+        serde_json::to_writer(&mut self.writer, &line)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(self.writer))
+            .with_context(|| format!("Failed to write log file {}", self.path.display()))
+    }
+}
+
+#[derive(Serialize)]
+struct LogLine<'a> {
+    time: String,
+    channel: Channel,
+    text: &'a str,
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with milliseconds.
+fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "{}.{:03}Z",
+        format_date_time(since_epoch.as_secs() as i64, 'T'),
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_time_with_milliseconds() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_792_230_067_253);
+
+        assert_eq!(format_time(time), "2026-10-17T09:41:07.253Z");
+    }
+
+    #[test]
+    fn serializes_lines_as_json_objects() {
+        let line = LogLine {
+            time: format_time(UNIX_EPOCH),
+            channel: Channel::App,
+            text: "Say \"hi\"\\\t\u{1b}[0m\n",
+        };
+
+        assert_eq!(
+            serde_json::to_string(&line).unwrap(),
+            r#"{"time":"1970-01-01T00:00:00.000Z","channel":"app","text":"Say \"hi\"\\\t\u001b[0m\n"}"#
+        );
+    }
+}
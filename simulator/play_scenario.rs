@@ -0,0 +1,21 @@
+//! Plays a scenario of shell commands to an application in the ConnectIQ
+//! simulator, checking what the application logs in response.
+//!
+//! Usage:
+//!   bazel run @rules_ciq//simulator:play_scenario <simulator_path> <shell_path> <application_id> <prg_path> <debug_xml_path> <settings_json_path> <device> --scenario <path>
+//!
+//! This tool performs the following steps:
+//! 1. Starts the ConnectIQ simulator.
+//! 2. Pushes the settings, debug XML and PRG to the simulator.
+//! 3. Starts the application.
+//! 4. Plays each step of the scenario, a JSON file of shell commands and
+//!    waits, interleaved with `expect_log` steps that wait for the
+//!    application to log a line containing some text.
+//! 5. Exits with 0 if every step succeeded and the application reported no
+//!    errors, or 1 otherwise.
+
+use lib::{run_simulator_operation, scenario};
+
+fn main() {
+    run_simulator_operation(scenario);
+}
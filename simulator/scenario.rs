@@ -0,0 +1,68 @@
+//! Scripts of shell commands to play to an app in the simulator, with checks
+//! on what it logs in response, read from JSON files such as:
+//!
+//! ```json
+//! {
+//!   "timeout": 10,
+//!   "steps": [
+//!     {"expect_log": "Ready"},
+//!     {"send": "<shell command>"},
+//!     {"expect_log": "Menu opened"},
+//!     {"wait": 0.5}
+//!   ]
+//! }
+//! ```
+//!
+//! The simulator shell's protocol is undocumented, so scenarios give any
+//! commands simulating input themselves, as `send` steps.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Seconds to wait for each `expect_log` step.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    pub steps: Vec<ScenarioStep>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScenarioStep {
+    /// Lets the app run for this many seconds.
+    Wait(f64),
+    /// Waits for the app to log a line containing this text, failing after
+    /// the scenario's timeout. Lines logged since the previous `expect_log`
+    /// are also searched, so that the step can follow the input causing it.
+    ExpectLog(String),
+    /// Sends this command to the shell as is.
+    Send(String),
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+impl Scenario {
+    /// Reads the scenario in the JSON file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse scenario {}", path.display()))
+    }
+}
+
+impl fmt::Display for ScenarioStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioStep::Wait(seconds) => write!(f, "wait {}s", seconds),
+            ScenarioStep::ExpectLog(text) => write!(f, "expect log '{}'", text),
+            ScenarioStep::Send(command) => write!(f, "send '{}'", command),
+        }
+    }
+}
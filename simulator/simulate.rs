@@ -11,7 +11,24 @@
 //! 5. Starts the application on the specified device.
 //! 6. Streams logs from the simulator to stdout, filtering and formatting them.
 //!
+//! `--filter <CHANNELS>` limits the output to a comma-separated list of the
+//! channels `simulator`, `device`, `app`, `test` and `raw`, and
+//! `--log-file <PATH>` additionally writes every line, with its channel and
+//! wall-clock time, to a JSON-lines file.
+//!
+//! With `--watch`, whenever the PRG, settings or debug XML change, it closes
+//! its shell, pushes the changed files through a new one, reopens the device
+//! and restarts the application, without restarting the simulator.
+//!
+//! Errors the application reports, such as unhandled exceptions, are
+//! summarised as `[CRASH]` lines with their stacks resolved using the debug
+//! XML, and make it exit with 1 once the simulator is closed.
+//!
 //! On macOS, it also attempts to bring the simulator window to the foreground.
+//!
+//! Each step gives up after a timeout, set with `--connect-timeout`,
+//! `--push-timeout`, `--shell-connected-timeout` and `--device-started-timeout`,
+//! and reports the last lines of shell output.
 
 use lib::{run_simulator_operation, simulate};
 
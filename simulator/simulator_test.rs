@@ -0,0 +1,700 @@
+//! End-to-end tests of the simulator tools against `fake_shell`.
+//!
+//! The tools and the fake shell are found through the `SIMULATE_TOOL`,
+//! `TEST_TOOL`, `SWEEP_TOOL`, `SMOKE_TEST_TOOL`, `PLAY_SCENARIO_TOOL` and
+//! `FAKE_SHELL` environment variables. The simulator itself is replaced with
+//! `true`, since the fake shell does not need one running.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const APPLICATION_ID: &str = "a3421fee-d289-106a-538c-b9547ab12095";
+const FORMATTED_APPLICATION_ID: &str = "A3421FEED289106A538CB9547AB12095";
+const DEVICE: &str = "fenix7";
+
+/// Inputs and outputs of one run of a tool, in a directory of its own.
+struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let base = env::var_os("TEST_TMPDIR").map_or_else(env::temp_dir, PathBuf::from);
+        let dir = base.join(format!(
+            "simulator_test-{}-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("pushed")).unwrap();
+        fs::write(dir.join("app.prg"), b"prg").unwrap();
+        fs::write(dir.join("app.prg.debug.xml"), b"<debugInfo/>").unwrap();
+        fs::write(dir.join("app-settings.json"), b"{}").unwrap();
+        Self { dir }
+    }
+
+    /// Sets the lines the fake shell prints once the app starts.
+    fn script<S: AsRef<str>>(&self, lines: &[S]) {
+        let script: Vec<&str> = lines.iter().map(AsRef::as_ref).collect();
+        fs::write(self.dir.join("script.txt"), script.join("\n")).unwrap();
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Returns the commands the fake shell received.
+    fn commands(&self) -> Vec<String> {
+        fs::read_to_string(self.path("commands.txt"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Runs the tool named by the environment variable `tool` with `extra_args`
+    /// and `envs`.
+    fn run(&self, tool: &str, extra_args: &[&str], envs: &[(&str, &str)]) -> Output {
+        self.command(tool, extra_args, envs).output().unwrap()
+    }
+
+    /// Returns the command that [`Fixture::run`] runs.
+    fn command(&self, tool: &str, extra_args: &[&str], envs: &[(&str, &str)]) -> Command {
+        let tool = env::var_os(tool).unwrap_or_else(|| panic!("{} is not set", tool));
+        let fake_shell = env::var_os("FAKE_SHELL").expect("FAKE_SHELL is not set");
+
+        let mut command = Command::new(tool);
+        command
+            .arg("true")
+            .arg(fake_shell)
+            .arg(APPLICATION_ID)
+            .arg(self.path("app.prg"))
+            .arg(self.path("app.prg.debug.xml"))
+            .arg(self.path("app-settings.json"))
+            .arg(DEVICE)
+            .args(extra_args)
+            // Bazel sets these for this test, not for the tools under test.
+            .env_remove("XML_OUTPUT_FILE")
+            .env_remove("TESTBRIDGE_TEST_ONLY")
+            .env_remove("TEST_TIMEOUT")
+            .env("FAKE_SHELL_SCRIPT", self.path("script.txt"))
+            .env("FAKE_SHELL_LOG", self.path("commands.txt"))
+            .env("FAKE_SHELL_PUSH_DIR", self.path("pushed"))
+            // Fails any attempt to connect a second shell while one is
+            // connected.
+            .env("FAKE_SHELL_LOCK", self.path("shell.lock"));
+        for (key, value) in envs {
+            command.env(key, value);
+        }
+        command
+    }
+
+    /// Waits up to five seconds for the fake shell to receive `count`
+    /// commands starting with `prefix`, returning whether it did.
+    fn wait_for_commands(&self, prefix: &str, count: usize) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            let received = self
+                .commands()
+                .iter()
+                .filter(|command| command.starts_with(prefix))
+                .count();
+            if received >= count {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        false
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Returns the output of a run of `sampleTest`, which passes, and
+/// `failingTest`, which passes only if `passing` is set.
+fn test_script(passing: bool) -> Vec<String> {
+    let test = format!("[4][{}][0]", FORMATTED_APPLICATION_ID);
+    let app = format!("[3][{}][0]", FORMATTED_APPLICATION_ID);
+    let status = if passing { "PASS" } else { "FAIL" };
+    let summary = if passing {
+        "PASSED (passed=2, failed=0, errors=0)"
+    } else {
+        "FAILED (passed=1, failed=1, errors=0)"
+    };
+    vec![
+        format!("{}Executing test sampleTest...", test),
+        format!("{}Hello from the app\\n", app),
+        format!("{}PASS", test),
+        format!("{}Executing test failingTest...", test),
+        format!("{}ASSERTION FAILED", test),
+        format!("{}{}", test, status),
+        format!("{}RESULTS", test),
+        format!("{}sampleTest    PASS", test),
+        format!("{}failingTest    {}", test, status),
+        format!("{}{}", test, summary),
+        "[1][0]deviceTerminated".to_string(),
+    ]
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn simulate_pushes_app_and_streams_logs() {
+    let fixture = Fixture::new("simulate");
+    fixture.script(&[
+        "[1][0]appStarted",
+        "[2][0]Device message",
+        &format!("[3][{}][0]Hello from the app\\n", FORMATTED_APPLICATION_ID),
+    ]);
+
+    let output = fixture.run("SIMULATE_TOOL", &[], &[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("[SIMULATOR] appStarted\n"), "{}", stdout);
+    assert!(stdout.contains("[DEVICE] Device message\n"), "{}", stdout);
+    assert!(stdout.contains("Hello from the app\n"), "{}", stdout);
+
+    let commands = fixture.commands();
+    assert_eq!(commands.len(), 6, "{:?}", commands);
+    assert!(commands[0].ends_with("\"0:/GARMIN/Settings/APP-settings.json\""));
+    assert!(commands[1].ends_with("\"0:/GARMIN/Debug/APP.PRG.DEBUG.XML\""));
+    assert!(commands[2].ends_with("\"0:/GARMIN/APPS/app.prg\""));
+    assert_eq!(commands[3], "ciq");
+    assert_eq!(commands[4], format!("[1][0]openDevice {}", DEVICE));
+    assert_eq!(
+        commands[5],
+        format!("[2][0]startApp {}", FORMATTED_APPLICATION_ID)
+    );
+
+    let pushed = fixture.path("pushed/GARMIN");
+    assert_eq!(fs::read(pushed.join("APPS/app.prg")).unwrap(), b"prg");
+    assert!(pushed.join("Settings/APP-settings.json").exists());
+    assert!(pushed.join("Debug/APP.PRG.DEBUG.XML").exists());
+}
+
+#[test]
+fn simulate_filters_channels_and_writes_log_file() {
+    let fixture = Fixture::new("log_file");
+    fixture.script(&[
+        "[1][0]appStarted",
+        &format!("[3][{}][0]Hello \"app\"\\n", FORMATTED_APPLICATION_ID),
+    ]);
+    let log_file = fixture.path("session.jsonl");
+
+    let output = fixture.run(
+        "SIMULATE_TOOL",
+        &["--filter", "app", "--log-file", log_file.to_str().unwrap()],
+        &[],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "Hello \"app\"\n");
+
+    let log = fs::read_to_string(log_file).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", log);
+    assert!(lines[0].starts_with(r#"{"time":""#), "{}", lines[0]);
+    assert!(
+        lines[0].ends_with(r#"Z","channel":"simulator","text":"appStarted"}"#),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[1].ends_with(r#""channel":"app","text":"Hello \"app\"\n"}"#),
+        "{}",
+        lines[1]
+    );
+}
+
+#[test]
+fn simulate_watch_pushes_changed_files_and_restarts_app() {
+    let fixture = Fixture::new("watch");
+    fixture.script(&["$HANG"]);
+
+    let mut child = fixture
+        .command("SIMULATE_TOOL", &["--watch"], &[])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = fixture.wait_for_commands("[2][0]startApp", 1);
+    if started {
+        fs::write(fixture.path("app.prg"), b"rebuilt prg").unwrap();
+    }
+    let restarted = started && fixture.wait_for_commands("[2][0]startApp", 2);
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(restarted, "{:?}", fixture.commands());
+    let pushes: Vec<String> = fixture
+        .commands()
+        .into_iter()
+        .filter(|command| command.starts_with("push "))
+        .collect();
+    assert_eq!(pushes.len(), 4, "{:?}", pushes);
+    assert!(pushes[3].ends_with("\"0:/GARMIN/APPS/app.prg\""));
+    assert_eq!(
+        fs::read(fixture.path("pushed/GARMIN/APPS/app.prg")).unwrap(),
+        b"rebuilt prg"
+    );
+}
+
+#[test]
+fn simulate_skips_missing_debug_xml() {
+    let fixture = Fixture::new("no_debug_xml");
+    fixture.script::<&str>(&[]);
+    fs::remove_file(fixture.path("app.prg.debug.xml")).unwrap();
+
+    let output = fixture.run("SIMULATE_TOOL", &[], &[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let commands = fixture.commands();
+    assert!(!commands.iter().any(|c| c.contains("/GARMIN/Debug/")));
+}
+
+/// Returns the lines in which the simulator reports an unhandled exception at
+/// PC 0x10000bd8, as resolved by [`CRASH_DEBUG_XML`].
+fn crash_script() -> Vec<String> {
+    let app = format!("[3][{}][0]", FORMATTED_APPLICATION_ID);
+    vec![
+        format!("{}Started\\n", app),
+        format!(
+            "{}Error: Unhandled Exception\\nException: UnexpectedTypeException\\nStack: \\n",
+            app
+        ),
+        format!("{}  - pc: 0x10000bd8\\n    Line: 25\\n", app),
+        format!("{}Encountered app crash.\\n", app),
+    ]
+}
+
+const CRASH_DEBUG_XML: &str = r#"<debugInfo><pcToLineNum>
+<entry filename="source/HelloView.mc" id="1" lineNum="25" parent="globals/HelloView" pc="268438488" symbol="onUpdate"/>
+</pcToLineNum></debugInfo>"#;
+
+#[test]
+fn simulate_reports_crash_with_resolved_stack() {
+    let fixture = Fixture::new("crash");
+    fixture.script(&crash_script());
+    fs::write(fixture.path("app.prg.debug.xml"), CRASH_DEBUG_XML).unwrap();
+
+    let output = fixture.run("SIMULATE_TOOL", &[], &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("The app reported 1 error(s)"),
+        "{}",
+        stderr(&output)
+    );
+    let stdout = stdout(&output);
+    let report: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("[CRASH]"))
+        .collect();
+    assert_eq!(
+        report,
+        [
+            "[CRASH] Error: Unhandled Exception",
+            "[CRASH]   Exception: UnexpectedTypeException",
+            "[CRASH]   at HelloView.onUpdate (source/HelloView.mc:25) [pc 0x10000bd8]",
+        ],
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn smoke_test_passes_if_app_runs_without_errors() {
+    let fixture = Fixture::new("smoke_test_pass");
+    fixture.script(&[
+        format!("[3][{}][0]Started\\n", FORMATTED_APPLICATION_ID),
+        "$HANG".to_string(),
+    ]);
+
+    let output = fixture.run("SMOKE_TEST_TOOL", &["--smoke-test-run-time", "1"], &[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("The app ran for 1 seconds without reporting errors"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn smoke_test_fails_on_error() {
+    let fixture = Fixture::new("smoke_test_fail");
+    let mut script = crash_script();
+    script.push("$HANG".to_string());
+    fixture.script(&script);
+
+    let output = fixture.run("SMOKE_TEST_TOOL", &["--smoke-test-run-time", "1"], &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("The app reported 1 error(s)"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stdout(&output).contains("[CRASH] Error: Unhandled Exception"));
+}
+
+#[test]
+fn smoke_test_fails_if_simulator_stops_early() {
+    let fixture = Fixture::new("smoke_test_stopped");
+    fixture.script::<&str>(&[]);
+
+    let output = fixture.run("SMOKE_TEST_TOOL", &["--smoke-test-run-time", "5"], &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("The simulator stopped before the app had run for 5 seconds"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn test_passes_and_writes_junit_xml() {
+    let fixture = Fixture::new("test_pass");
+    fixture.script(&test_script(true));
+    let xml_output_file = fixture.path("test.xml");
+
+    let output = fixture.run(
+        "TEST_TOOL",
+        &[],
+        &[("XML_OUTPUT_FILE", xml_output_file.to_str().unwrap())],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fixture.commands().last().unwrap(),
+        &format!("[2][0]runAllTests {}", FORMATTED_APPLICATION_ID)
+    );
+    let xml = fs::read_to_string(xml_output_file).unwrap();
+    assert!(
+        xml.contains(r#"tests="2" failures="0" errors="0""#),
+        "{}",
+        xml
+    );
+    assert!(xml.contains("<system-out>Hello from the app\n"), "{}", xml);
+}
+
+#[test]
+fn test_fails_and_names_failed_tests() {
+    let fixture = Fixture::new("test_fail");
+    fixture.script(&test_script(false));
+    let xml_output_file = fixture.path("test.xml");
+
+    let output = fixture.run(
+        "TEST_TOOL",
+        &[],
+        &[("XML_OUTPUT_FILE", xml_output_file.to_str().unwrap())],
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("Not all tests passed: failingTest"),
+        "{}",
+        stderr(&output)
+    );
+    let xml = fs::read_to_string(xml_output_file).unwrap();
+    assert!(
+        xml.contains(r#"<failure message="ASSERTION FAILED">"#),
+        "{}",
+        xml
+    );
+}
+
+#[test]
+fn test_filter_skips_unselected_tests() {
+    let fixture = Fixture::new("test_filter");
+    fixture.script(&test_script(false));
+
+    let output = fixture.run("TEST_TOOL", &["--test-filter", "sample*"], &[]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("Skipped 1 test(s) not matching 'sample*': failingTest"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn test_fails_when_no_tests_match_filter() {
+    let fixture = Fixture::new("test_filter_none");
+    fixture.script(&test_script(true));
+
+    let output = fixture.run("TEST_TOOL", &["--test-filter", "missing"], &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("No tests match 'missing'"));
+}
+
+#[test]
+fn test_fails_without_outcome() {
+    let fixture = Fixture::new("test_no_outcome");
+    fixture.script(&["[1][0]deviceTerminated"]);
+
+    let output = fixture.run("TEST_TOOL", &[], &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("No test outcome found"));
+}
+
+#[test]
+fn simulate_times_out_waiting_for_device() {
+    let fixture = Fixture::new("device_timeout");
+    fixture.script::<&str>(&[]);
+
+    let output = fixture.run(
+        "SIMULATE_TOOL",
+        &["--device-started-timeout", "1"],
+        &[("FAKE_SHELL_HANG_ON", "[1][0]openDevice")],
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("Timed out after 1s waiting for the simulator to start fenix7"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains(":>[1][0]shellConnected"), "{}", stderr);
+}
+
+#[test]
+fn test_times_out_naming_running_test() {
+    let fixture = Fixture::new("test_timeout");
+    let mut script = test_script(true);
+    script.truncate(4);
+    script.push("$HANG".to_string());
+    fixture.script(&script);
+
+    let output = fixture.run("TEST_TOOL", &["--test-timeout", "1"], &[]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("Timed out after 1s running tests (test failingTest was running)"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("Executing test failingTest..."),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn sweep_runs_each_device_and_reports_errors() {
+    let fixture = Fixture::new("sweep");
+    fs::write(fixture.path("fr955.prg"), b"fr955 prg").unwrap();
+    fs::write(fixture.path("fr955-settings.json"), b"{}").unwrap();
+    fixture.script(&[
+        format!("[3][{}][0]Started\\n", FORMATTED_APPLICATION_ID),
+        format!(
+            "@fr955 [3][{}][0]Error: Unhandled Exception\\n",
+            FORMATTED_APPLICATION_ID
+        ),
+        "$HANG".to_string(),
+    ]);
+    let fr955 = format!("fr955={}", fixture.path("fr955.prg").display());
+
+    let output = fixture.run(
+        "SWEEP_TOOL",
+        &[
+            "--sweep",
+            &fr955,
+            "--sweep-run-time",
+            "1",
+        ],
+        &[],
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("1 of 2 devices failed"),
+        "{}",
+        stderr(&output)
+    );
+    let stdout = stdout(&output);
+    let summary: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("DEVICE"))
+        .collect();
+    assert_eq!(summary.len(), 3, "{}", stdout);
+    assert!(summary[1].starts_with("fenix7  OK "), "{}", stdout);
+    assert!(summary[2].starts_with("fr955   ERRORS "), "{}", stdout);
+    assert!(
+        summary[2].ends_with("Error: Unhandled Exception"),
+        "{}",
+        stdout
+    );
+
+    // Each build is pushed just before its device is opened, through a new
+    // session once the previous one has ended.
+    let commands = fixture.commands();
+    let position = |prefix: &str| {
+        commands
+            .iter()
+            .position(|command| command.starts_with(prefix))
+            .unwrap_or_else(|| panic!("{} not sent: {:?}", prefix, commands))
+    };
+    let fr955_push = commands
+        .iter()
+        .position(|command| command.ends_with("\"0:/GARMIN/APPS/fr955.prg\""))
+        .unwrap();
+    assert!(position("[2][0]startApp") < fr955_push, "{:?}", commands);
+    assert!(fr955_push < position("[1][0]openDevice fr955"), "{:?}", commands);
+    assert!(fixture.path("pushed/GARMIN/APPS/fr955.prg").exists());
+}
+
+#[test]
+fn sweep_reports_devices_when_simulator_stops() {
+    let fixture = Fixture::new("sweep_stopped");
+    for device in ["fr955", "venu2"] {
+        fs::write(fixture.path(&format!("{}.prg", device)), b"prg").unwrap();
+        fs::write(fixture.path(&format!("{}-settings.json", device)), b"{}").unwrap();
+    }
+    // The script ends, and with it the shell, once fr955 is started.
+    fixture.script(&[
+        format!("[3][{}][0]Started\\n", FORMATTED_APPLICATION_ID),
+        "@fenix7 $HANG".to_string(),
+    ]);
+    let fr955 = format!("fr955={}", fixture.path("fr955.prg").display());
+    let venu2 = format!("venu2={}", fixture.path("venu2.prg").display());
+
+    let output = fixture.run(
+        "SWEEP_TOOL",
+        &["--sweep", &fr955, "--sweep", &venu2, "--sweep-run-time", "1"],
+        &[],
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stderr(&output).contains("2 of 3 devices failed"),
+        "{}",
+        stderr(&output)
+    );
+    let stdout = stdout(&output);
+    let summary: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("DEVICE"))
+        .collect();
+    assert_eq!(summary.len(), 4, "{}", stdout);
+    assert!(summary[1].starts_with("fenix7  OK "), "{}", stdout);
+    assert!(summary[2].starts_with("fr955   FAILED "), "{}", stdout);
+    assert!(
+        summary[2].ends_with("The simulator stopped while running on fr955"),
+        "{}",
+        stdout
+    );
+    assert!(summary[3].starts_with("venu2   FAILED "), "{}", stdout);
+    assert!(
+        summary[3].ends_with("Not run, as the simulator stopped"),
+        "{}",
+        stdout
+    );
+    assert!(!fixture.path("pushed/GARMIN/APPS/venu2.prg").exists());
+}
+
+#[test]
+fn scenario_sends_commands_and_checks_logs() {
+    let fixture = Fixture::new("scenario");
+    let app = format!("[3][{}][0]", FORMATTED_APPLICATION_ID);
+    fixture.script(&[
+        format!("{}Ready\\n", app),
+        "$AWAIT open menu".to_string(),
+        format!("{}Menu opened\\n", app),
+        "$AWAIT next page".to_string(),
+        format!("{}Page 2\\n", app),
+        "$HANG".to_string(),
+    ]);
+    fs::write(
+        fixture.path("scenario.json"),
+        r#"{
+            "steps": [
+                {"expect_log": "Ready"},
+                {"send": "open menu"},
+                {"expect_log": "Menu opened"},
+                {"send": "next page"},
+                {"wait": 0.5},
+                {"expect_log": "Page 2"}
+            ]
+        }"#,
+    )
+    .unwrap();
+    let scenario = fixture.path("scenario.json");
+
+    let output = fixture.run(
+        "PLAY_SCENARIO_TOOL",
+        &["--scenario", scenario.to_str().unwrap()],
+        &[],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("[SCENARIO] Step 6/6: expect log 'Page 2'"),
+        "{}",
+        stdout(&output)
+    );
+    let commands = fixture.commands();
+    let sent: Vec<&String> = commands
+        .iter()
+        .filter(|command| ["open menu", "next page"].contains(&command.as_str()))
+        .collect();
+    assert_eq!(sent, ["open menu", "next page"]);
+}
+
+#[test]
+fn scenario_fails_when_expected_log_is_missing() {
+    let fixture = Fixture::new("scenario_missing_log");
+    fixture.script(&[
+        format!("[3][{}][0]Ready\\n", FORMATTED_APPLICATION_ID),
+        "$HANG".to_string(),
+    ]);
+    fs::write(
+        fixture.path("scenario.json"),
+        r#"{"timeout": 1, "steps": [{"send": "open menu"}, {"expect_log": "Menu opened"}]}"#,
+    )
+    .unwrap();
+    let scenario = fixture.path("scenario.json");
+
+    let output = fixture.run(
+        "PLAY_SCENARIO_TOOL",
+        &["--scenario", scenario.to_str().unwrap()],
+        &[],
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("Scenario step 2 (expect log 'Menu opened') failed"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("Timed out after 1s waiting for the app to log 'Menu opened'"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,20 @@
+//! Runs an application in the ConnectIQ simulator for a while, failing if it
+//! reports an error.
+//!
+//! Usage:
+//!   bazel test @rules_ciq//simulator:smoke_test <simulator_path> <shell_path> <application_id> <prg_path> <debug_xml_path> <settings_json_path> <device> [--smoke-test-run-time <seconds>]
+//!
+//! This tool performs the following steps:
+//! 1. Starts the ConnectIQ simulator.
+//! 2. Pushes the settings, debug XML and PRG to the simulator.
+//! 3. Starts the application and streams its logs for `--smoke-test-run-time`
+//!    seconds, reporting any errors such as unhandled exceptions with their
+//!    stacks resolved using the debug XML.
+//! 4. Exits with 0 if the application ran without reporting errors, or 1
+//!    otherwise.
+
+use lib::{run_simulator_operation, smoke_test};
+
+fn main() {
+    run_simulator_operation(smoke_test);
+}
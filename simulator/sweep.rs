@@ -0,0 +1,21 @@
+//! Runs an application on several devices in one ConnectIQ simulator session.
+//!
+//! Usage:
+//!   bazel run @rules_ciq//simulator:sweep <simulator_path> <shell_path> <application_id> <prg_path> <debug_xml_path> <settings_json_path> <device> [--sweep <device>=<prg_path> ...]
+//!
+//! This tool performs the following steps:
+//! 1. Starts the ConnectIQ simulator.
+//! 2. Pushes the settings, debug XML and PRG of the first build to the
+//!    simulator.
+//! 3. For each device in turn, pushes its build through a new shell once the
+//!    previous device's has been closed, opens it, starts the application
+//!    and lets it run for `--sweep-run-time` seconds, noting any errors it
+//!    logs. If the simulator stops, the devices left are not run.
+//! 4. Prints a summary table of the outcome on each device.
+//! 5. Exits with 0 if no device failed or logged errors, or 1 otherwise.
+
+use lib::{run_simulator_operation, sweep};
+
+fn main() {
+    run_simulator_operation(sweep);
+}
@@ -11,6 +11,18 @@
 //! 5. Triggers the "Run No Evil" tests for the application.
 //! 6. Streams logs and monitors for test results.
 //! 6. Exits with 0 if all tests pass, or 1 otherwise.
+//!
+//! When run by `bazel test`, per-test results are also written as JUnit XML to
+//! `$XML_OUTPUT_FILE`.
+//!
+//! `--test-filter <PATTERNS>`, or Bazel's `--test_filter` through
+//! `$TESTBRIDGE_TEST_ONLY`, selects tests by name. All tests still run, but
+//! those not selected are reported as skipped and their results are ignored.
+//!
+//! Each step gives up after a timeout, set with `--connect-timeout`,
+//! `--push-timeout`, `--shell-connected-timeout`, `--device-started-timeout` and
+//! `--test-timeout`, and reports the last lines of shell output. The test
+//! timeout defaults to shortly before Bazel's own.
 
 use lib::{run_simulator_operation, test};
 
@@ -0,0 +1,68 @@
+//! Detection of rebuilt app files, for `simulate --watch`.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// When a file was last modified, and its size.
+type Stamp = (SystemTime, u64);
+
+struct WatchedFile {
+    path: PathBuf,
+    /// The stamp of the file when last polled.
+    seen: Option<Stamp>,
+    /// The stamp of the file when its last change was reported.
+    reported: Option<Stamp>,
+}
+
+/// Polls files for changes.
+pub struct FileWatcher {
+    files: Vec<WatchedFile>,
+}
+
+impl FileWatcher {
+    /// Starts watching `paths`, taking their current contents as unchanged.
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let current = stamp(&path);
+                WatchedFile {
+                    path,
+                    seen: current,
+                    reported: current,
+                }
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// Returns the files that changed since they were last reported.
+    ///
+    /// Nothing is reported while any file is still changing between polls, so
+    /// that the files of one rebuild are reported together, once complete.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut settled = true;
+        for file in &mut self.files {
+            let current = stamp(&file.path);
+            settled &= current == file.seen;
+            file.seen = current;
+        }
+        if !settled {
+            return Vec::new();
+        }
+
+        self.files
+            .iter_mut()
+            .filter(|file| file.seen != file.reported)
+            .map(|file| {
+                file.reported = file.seen;
+                file.path.clone()
+            })
+            .collect()
+    }
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}